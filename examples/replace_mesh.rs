@@ -1,9 +1,9 @@
 use bevy::DefaultPlugins;
 use bevy::asset::AssetServer;
-use bevy::pbr::{ExtendedMaterial, MaterialExtension};
+use bevy::pbr::MaterialExtension;
 use bevy::prelude::{
-    App, AppExtStates, Asset, Camera3d, Commands, MaterialPlugin, NextState, OnEnter, PointLight,
-    Reflect, Res, ResMut, StandardMaterial, States, Transform, Vec3, default,
+    App, AppExtStates, Asset, Camera3d, Commands, NextState, OnEnter, PointLight, Reflect, Res,
+    ResMut, States, Transform, Vec3, default,
 };
 use bevy::render::render_resource::{AsBindGroup, ShaderRef};
 use bevy_runtime_gltf_loader::{SimpleRuntimeGltfLoaderPlugin, SimplePartsMap};
@@ -19,9 +19,6 @@ enum RuntimeState {
 pub fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_plugins(MaterialPlugin::<
-            ExtendedMaterial<StandardMaterial, MyMaterialExtension>,
-        >::default())
         .init_state::<RuntimeState>()
         .add_plugins(
             SimpleRuntimeGltfLoaderPlugin::default()
@@ -30,7 +27,8 @@ pub fn main() {
                     RuntimeState::Preload,
                     RuntimeState::SceneSetup,
                 )
-                .register_material_extension::<MyMaterialExtension>(),
+                .register_material_extension::<MyMaterialExtension>()
+                .add_material_plugins(),
         )
         .add_systems(OnEnter(RuntimeState::SceneSetup), setup_scene)
         .run();
@@ -0,0 +1,136 @@
+use crate::part_transform::PartTransform;
+use crate::parts_map::PartsMap;
+use bevy::asset::{Asset, AssetServer};
+use bevy::prelude::{ChildOf, Commands, Entity, Res, Transform};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Display;
+
+/// Multi part object read from the `$assemblies` section of a config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assembly {
+    pub root: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<AssemblyPart>,
+}
+
+/// Part spawned as a child of the part above it in the assembly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssemblyPart {
+    pub part: String,
+    // Named node of the parent part to attach to, the parent itself is used when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<String>,
+    // Offset from the parent or socket, applied on top of the part transform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<PartTransform>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<AssemblyPart>,
+}
+
+impl Assembly {
+    // Every part used by the assembly, the root first
+    pub(crate) fn parts(&self) -> Vec<&str> {
+        let mut parts = vec![self.root.as_str()];
+        let mut stack: Vec<&AssemblyPart> = self.children.iter().collect();
+        while let Some(child) = stack.pop() {
+            parts.push(&child.part);
+            stack.extend(child.children.iter());
+        }
+        parts
+    }
+}
+
+/// Spawns an assembly with one `PartLoader` per part, created by `PartsMap::assemble`
+pub struct AssemblyLoader<'a, EXTENSION: Asset> {
+    parts_map: &'a PartsMap<EXTENSION>,
+    assembly: &'a Assembly,
+    on: Option<Entity>,
+    offset: Option<Transform>,
+}
+
+impl<'a, EXTENSION: Asset> AssemblyLoader<'a, EXTENSION> {
+    pub(crate) fn new(parts_map: &'a PartsMap<EXTENSION>, assembly: &'a Assembly) -> Self {
+        Self {
+            parts_map,
+            assembly,
+            on: None,
+            offset: None,
+        }
+    }
+
+    pub fn on(mut self, entity: Entity) -> Self {
+        self.on = Some(entity);
+        self
+    }
+
+    pub fn offset(mut self, offset: Transform) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Spawns the root part and every child part, returns the root entity
+    pub fn build(self, commands: &mut Commands, asset_server: &Res<AssetServer>) -> Entity {
+        let mut root = self.parts_map[self.assembly.root.as_str()].load();
+        if let Some(on) = self.on {
+            root = root.on(on);
+        }
+        if let Some(offset) = self.offset {
+            root = root.offset(offset);
+        }
+        let root = root.build(commands, asset_server);
+
+        for child in self.assembly.children.iter() {
+            self.build_child(child, root, commands, asset_server);
+        }
+
+        root
+    }
+
+    fn build_child(
+        &self,
+        child: &AssemblyPart,
+        parent: Entity,
+        commands: &mut Commands,
+        asset_server: &Res<AssetServer>,
+    ) {
+        let mut loader = self.parts_map[child.part.as_str()].load();
+        if let Some(transform) = child.transform {
+            loader = loader.offset(transform.into());
+        }
+        if let Some(socket) = &child.socket {
+            loader = loader.on_socket(parent, socket);
+        }
+        let entity = loader.build(commands, asset_server);
+
+        if child.socket.is_none() {
+            commands.entity(entity).insert(ChildOf(parent));
+        }
+
+        for grandchild in child.children.iter() {
+            self.build_child(grandchild, entity, commands, asset_server);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AssemblyNotFoundError {
+    pub assembly: String,
+    // Part used by the assembly that is not in the `PartsMap`
+    pub part: Option<String>,
+}
+
+impl Display for AssemblyNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.part {
+            Some(part) => write!(
+                f,
+                "unable to read {} part of {} assembly",
+                part, &self.assembly
+            ),
+            None => write!(f, "unable to read {} assembly", &self.assembly),
+        }
+    }
+}
+
+impl Error for AssemblyNotFoundError {}
@@ -0,0 +1,728 @@
+use crate::assembly::Assembly;
+use crate::glob::glob_match;
+use crate::part_path::PartPath;
+use crate::parts_map::PartsMap;
+use crate::templates::{PartKeys, resolve_templates};
+use bevy::asset::io::Reader;
+use bevy::asset::{Asset, AssetLoader, AssetPath, AssetServer, LoadContext};
+use bevy::prelude::{Event, Resource};
+use bevy::tasks::futures_lite::StreamExt;
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Sent when a config file could not be read or deserialized into a `PartsMap`
+/// The last error is also kept around as a resource
+#[derive(Event, Resource, Debug, Clone)]
+pub struct ConfigLoadError {
+    pub file: String,
+    // The part whose entry failed to deserialize
+    pub part: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Display for ConfigLoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unable to load config {}", &self.file)?;
+        if let Some(part) = &self.part {
+            write!(f, " at part {}", part)?;
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, " (line {}, column {})", line, column)?;
+        }
+        write!(f, ": {}", &self.message)
+    }
+}
+
+impl Error for ConfigLoadError {}
+
+impl ConfigLoadError {
+    pub(crate) fn new(file: &str, message: impl Display) -> Self {
+        Self {
+            file: file.to_string(),
+            part: None,
+            line: None,
+            column: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+// The asset server only exposes loader errors as text, so the loader shares the typed errors through here
+// Errors are keyed by the loaded file, which is not the erroring file when the error comes from an include
+#[derive(Resource, Clone, Default)]
+pub(crate) struct ConfigErrorQueue(Arc<Mutex<Vec<(String, ConfigLoadError)>>>);
+
+impl ConfigErrorQueue {
+    // Only the last error of a file is kept so failing reloads that are never reported do not pile up
+    fn push(&self, file: &str, error: ConfigLoadError) {
+        if let Ok(mut errors) = self.0.lock() {
+            errors.retain(|(key, _)| key != file);
+            errors.push((file.to_string(), error));
+        }
+    }
+
+    pub(crate) fn take(&self, file: &str) -> Option<ConfigLoadError> {
+        let mut errors = self.0.lock().ok()?;
+        let index = errors.iter().position(|(key, _)| key == file)?;
+        Some(errors.remove(index).1)
+    }
+}
+
+// Only the data of a part, read as an untyped value
+#[derive(Deserialize)]
+#[serde(rename = "PartPath")]
+struct RawData {
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct PartsMapLoaderSettings {
+    // Files that are currently including this one, used to detect include cycles
+    include_chain: Vec<String>,
+}
+
+// Reads JSON, RON, TOML and YAML configs in place of the `bevy_common_assets` loaders
+// Those only hand back the format error, this one also needs to name the failing part with its line and column,
+// load `$include` files through the `LoadContext`, merge templates and read the `$assemblies` section
+pub(crate) struct PartsMapLoader<EXTENSION> {
+    extensions: Vec<&'static str>,
+    errors: ConfigErrorQueue,
+    // Needed to list directories for include patterns
+    asset_server: AssetServer,
+    phantom_data: PhantomData<EXTENSION>,
+}
+
+impl<EXTENSION> PartsMapLoader<EXTENSION> {
+    pub fn new(
+        extensions: &[&'static str],
+        errors: ConfigErrorQueue,
+        asset_server: AssetServer,
+    ) -> Self {
+        Self {
+            extensions: extensions.to_vec(),
+            errors,
+            asset_server,
+            phantom_data: Default::default(),
+        }
+    }
+}
+
+impl<EXTENSION> AssetLoader for PartsMapLoader<EXTENSION>
+where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+{
+    type Asset = PartsMap<EXTENSION>;
+    type Settings = PartsMapLoaderSettings;
+    type Error = ConfigLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &PartsMapLoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let file = load_context.path().to_string_lossy().to_string();
+
+        self.read_parts(reader, settings, load_context, &file)
+            .await
+            .inspect_err(|error| self.errors.push(&file, error.clone()))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+}
+
+impl<EXTENSION> PartsMapLoader<EXTENSION>
+where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+{
+    async fn read_parts(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &PartsMapLoaderSettings,
+        load_context: &mut LoadContext<'_>,
+        file: &str,
+    ) -> Result<PartsMap<EXTENSION>, ConfigLoadError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|error| ConfigLoadError::new(file, error))?;
+
+        let extension = load_context
+            .path()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        // Template keys are read first, only template parts are merged as untyped values
+        let mut includes = vec![];
+        let mut assemblies = HashMap::new();
+        let keys = parse::<PartKeys>(
+            extension,
+            &bytes,
+            &HashSet::new(),
+            &mut includes,
+            &mut assemblies,
+        )
+        .map_err(|error| error.into_config_error(file))?;
+        let templates: HashSet<String> = keys
+            .iter()
+            .filter(|(_, keys)| keys.is_template())
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        // Untyped RON values drop enum variant names, so merged parts and components could not be read back
+        if extension == "ron" {
+            if let Some(part) = templates.iter().min() {
+                return Err(ConfigLoadError {
+                    part: Some(part.clone()),
+                    ..ConfigLoadError::new(file, "templates are not supported in RON configs")
+                });
+            }
+
+            let components = keys.iter().filter(|(_, keys)| keys.components);
+            if let Some((part, _)) = components.min_by_key(|(name, _)| *name) {
+                return Err(ConfigLoadError {
+                    part: Some(part.clone()),
+                    ..ConfigLoadError::new(file, "components are not supported in RON configs")
+                });
+            }
+        }
+
+        let mut parts = parse::<PartPath<EXTENSION>>(
+            extension,
+            &bytes,
+            &templates,
+            &mut vec![],
+            &mut HashMap::new(),
+        )
+        .map_err(|error| error.into_config_error(file))?;
+
+        // Kept to tell whether a reloaded part changed, RON data is always treated as changed
+        if extension != "ron" {
+            let raw_data = parse::<RawData>(
+                extension,
+                &bytes,
+                &templates,
+                &mut vec![],
+                &mut HashMap::new(),
+            )
+            .map_err(|error| error.into_config_error(file))?;
+
+            for (name, raw) in raw_data {
+                if let Some(part) = parts.get_mut(&name) {
+                    part.raw_data = raw.data;
+                }
+            }
+        }
+
+        if !templates.is_empty() {
+            let raw = parse::<serde_json::Value>(
+                extension,
+                &bytes,
+                &HashSet::new(),
+                &mut vec![],
+                &mut HashMap::new(),
+            )
+            .map_err(|error| error.into_config_error(file))?;
+
+            let resolved =
+                resolve_templates::<EXTENSION>(raw).map_err(|(part, message)| ConfigLoadError {
+                    part: Some(part),
+                    ..ConfigLoadError::new(file, message)
+                })?;
+            parts.extend(resolved);
+        }
+
+        let mut parts = PartsMap {
+            parts,
+            sources: HashMap::new(),
+            assemblies,
+        };
+        for (name, part) in parts.parts.iter_mut() {
+            part.name = name.clone();
+        }
+
+        let source = normalize(load_context.path());
+        parts.sources = parts
+            .parts
+            .keys()
+            .map(|name| (name.clone(), source.clone()))
+            .collect();
+
+        let mut chain = settings.include_chain.clone();
+        chain.push(source);
+
+        for pattern in includes.iter() {
+            for path in self.resolve_include(load_context, file, pattern).await? {
+                check_include_cycle(&chain, &normalize(path.path()), file)?;
+
+                let included_chain = chain.clone();
+                let included = load_context
+                    .loader()
+                    .with_settings(move |settings: &mut PartsMapLoaderSettings| {
+                        settings.include_chain = included_chain.clone();
+                    })
+                    .immediate()
+                    .load::<PartsMap<EXTENSION>>(path.clone())
+                    .await
+                    .map_err(|error| {
+                        let included_file = path.path().to_string_lossy().to_string();
+                        self.errors
+                            .take(&included_file)
+                            .unwrap_or_else(|| ConfigLoadError::new(&included_file, error))
+                    })?;
+
+                merge_included(&mut parts, included.take(), file)?;
+            }
+        }
+
+        Ok(parts)
+    }
+
+    // Patterns are relative to the including file, `*` and `?` are only supported in the file name
+    async fn resolve_include(
+        &self,
+        load_context: &LoadContext<'_>,
+        file: &str,
+        pattern: &str,
+    ) -> Result<Vec<AssetPath<'static>>, ConfigLoadError> {
+        let path = load_context
+            .asset_path()
+            .resolve_embed(pattern)
+            .map_err(|error| ConfigLoadError::new(file, error))?;
+
+        let Some(file_pattern) = path
+            .path()
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.contains(['*', '?']))
+        else {
+            return Ok(vec![path]);
+        };
+
+        let directory = path.path().parent().unwrap_or(Path::new(""));
+        let source = self
+            .asset_server
+            .get_source(path.source().clone())
+            .map_err(|error| ConfigLoadError::new(file, error))?;
+        let mut entries = source
+            .reader()
+            .read_directory(directory)
+            .await
+            .map_err(|error| ConfigLoadError::new(file, error))?;
+
+        let mut paths = vec![];
+        while let Some(entry) = entries.next().await {
+            let matches = entry
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| glob_match(file_pattern, name));
+
+            if matches {
+                paths.push(
+                    AssetPath::from_path(&entry)
+                        .with_source(path.source().clone())
+                        .into_owned(),
+                );
+            }
+        }
+
+        // Directory order is platform dependent
+        paths.sort_by_key(|path| path.to_string());
+        Ok(paths)
+    }
+}
+
+fn check_include_cycle(
+    chain: &[String],
+    included_file: &str,
+    file: &str,
+) -> Result<(), ConfigLoadError> {
+    if chain.iter().any(|source| source == included_file) {
+        return Err(ConfigLoadError::new(
+            file,
+            format!("include cycle {} -> {}", chain.join(" -> "), included_file),
+        ));
+    }
+    Ok(())
+}
+
+// Moves the included parts into `parts`, a part coming from two different files is an error
+fn merge_included<EXTENSION: Asset>(
+    parts: &mut PartsMap<EXTENSION>,
+    included: PartsMap<EXTENSION>,
+    file: &str,
+) -> Result<(), ConfigLoadError> {
+    let PartsMap {
+        parts: included_parts,
+        sources: included_sources,
+        assemblies: included_assemblies,
+    } = included;
+
+    // Assemblies of the including file take priority
+    for (name, assembly) in included_assemblies {
+        parts.assemblies.entry(name).or_insert(assembly);
+    }
+
+    for (name, part) in included_parts {
+        let source = included_sources.get(&name).cloned().unwrap_or_default();
+
+        if let Some(existing) = parts.sources.get(&name) {
+            // The same file can be reached through more than one include
+            if *existing == source {
+                continue;
+            }
+
+            return Err(ConfigLoadError {
+                part: Some(name.clone()),
+                ..ConfigLoadError::new(
+                    file,
+                    format!(
+                        "part {} is defined in both {} and {}",
+                        name, existing, source
+                    ),
+                )
+            });
+        }
+
+        parts.sources.insert(name.clone(), source);
+        parts.parts.insert(name, part);
+    }
+
+    Ok(())
+}
+
+fn normalize(path: &Path) -> String {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .to_string()
+}
+
+struct ParseError {
+    part: Option<String>,
+    message: String,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+impl ParseError {
+    fn into_config_error(self, file: &str) -> ConfigLoadError {
+        ConfigLoadError {
+            file: file.to_string(),
+            part: self.part,
+            line: self.line,
+            column: self.column,
+            message: self.message,
+        }
+    }
+
+    // Most formats append the position to the message while it is already tracked on its own
+    fn new(message: String, line: usize, column: usize) -> Self {
+        let message = message
+            .strip_suffix(&format!(" at line {} column {}", line, column))
+            .map(str::to_string)
+            .unwrap_or(message);
+
+        Self {
+            part: None,
+            message,
+            line: Some(line),
+            column: Some(column),
+        }
+    }
+}
+
+fn parse<PART>(
+    extension: &str,
+    bytes: &[u8],
+    skipped: &HashSet<String>,
+    includes: &mut Vec<String>,
+    assemblies: &mut HashMap<String, Assembly>,
+) -> Result<HashMap<String, PART>, ParseError>
+where
+    for<'de> PART: serde::Deserialize<'de>,
+{
+    let mut part = None;
+    let seed = PartsMapSeed::<PART> {
+        failed_part: &mut part,
+        skipped,
+        includes,
+        assemblies,
+        phantom_data: Default::default(),
+    };
+
+    let parsed = match extension {
+        #[cfg(feature = "ron")]
+        "ron" => parse_ron(seed, bytes),
+        #[cfg(feature = "toml")]
+        "toml" => parse_toml(seed, bytes),
+        #[cfg(feature = "yaml")]
+        "yaml" | "yml" => parse_yaml(seed, bytes),
+        _ => parse_json(seed, bytes),
+    };
+
+    parsed.map_err(|error| ParseError { part, ..error })
+}
+
+#[cfg(feature = "toml")]
+impl From<std::str::Utf8Error> for ParseError {
+    fn from(error: std::str::Utf8Error) -> Self {
+        Self {
+            part: None,
+            message: error.to_string(),
+            line: None,
+            column: None,
+        }
+    }
+}
+
+fn parse_json<PART>(
+    seed: PartsMapSeed<PART>,
+    bytes: &[u8],
+) -> Result<HashMap<String, PART>, ParseError>
+where
+    for<'de> PART: serde::Deserialize<'de>,
+{
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    seed.deserialize(&mut deserializer)
+        .and_then(|parts| deserializer.end().map(|_| parts))
+        .map_err(|error| ParseError::new(error.to_string(), error.line(), error.column()))
+}
+
+#[cfg(feature = "ron")]
+fn parse_ron<PART>(
+    seed: PartsMapSeed<PART>,
+    bytes: &[u8],
+) -> Result<HashMap<String, PART>, ParseError>
+where
+    for<'de> PART: serde::Deserialize<'de>,
+{
+    let spanned = |error: ron::error::SpannedError| {
+        ParseError::new(
+            error.code.to_string(),
+            error.position.line,
+            error.position.col,
+        )
+    };
+
+    let mut deserializer = ron::Deserializer::from_bytes(bytes).map_err(spanned)?;
+    let parsed = seed
+        .deserialize(&mut deserializer)
+        .and_then(|parts| deserializer.end().map(|_| parts));
+
+    parsed.map_err(|error| spanned(deserializer.span_error(error)))
+}
+
+#[cfg(feature = "toml")]
+fn parse_toml<PART>(
+    seed: PartsMapSeed<PART>,
+    bytes: &[u8],
+) -> Result<HashMap<String, PART>, ParseError>
+where
+    for<'de> PART: serde::Deserialize<'de>,
+{
+    let text = std::str::from_utf8(bytes)?;
+
+    seed.deserialize(toml::Deserializer::new(text))
+        .map_err(|error| match error.span() {
+            Some(span) => {
+                // Toml only reports byte offsets
+                let before = &text[..span.start];
+                let line = before.matches('\n').count() + 1;
+                let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+                ParseError::new(error.message().to_string(), line, column)
+            }
+            None => ParseError {
+                part: None,
+                message: error.message().to_string(),
+                line: None,
+                column: None,
+            },
+        })
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml<PART>(
+    seed: PartsMapSeed<PART>,
+    bytes: &[u8],
+) -> Result<HashMap<String, PART>, ParseError>
+where
+    for<'de> PART: serde::Deserialize<'de>,
+{
+    seed.deserialize(serde_yaml::Deserializer::from_slice(bytes))
+        .map_err(|error| match error.location() {
+            Some(location) => {
+                ParseError::new(error.to_string(), location.line(), location.column())
+            }
+            None => ParseError {
+                part: None,
+                message: error.to_string(),
+                line: None,
+                column: None,
+            },
+        })
+}
+
+// Top level key listing other config files whose parts are merged into this one
+const INCLUDE_KEY: &str = "$include";
+// Top level key describing objects built from several parts
+const ASSEMBLIES_KEY: &str = "$assemblies";
+
+// Deserializes the map one part at a time so a failure can be traced back to its part
+struct PartsMapSeed<'a, PART> {
+    failed_part: &'a mut Option<String>,
+    // Parts read through another path, they are only checked to be valid syntax
+    skipped: &'a HashSet<String>,
+    includes: &'a mut Vec<String>,
+    assemblies: &'a mut HashMap<String, Assembly>,
+    phantom_data: PhantomData<PART>,
+}
+
+impl<'de, PART> DeserializeSeed<'de> for PartsMapSeed<'_, PART>
+where
+    PART: Deserialize<'de>,
+{
+    type Value = HashMap<String, PART>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, PART> Visitor<'de> for PartsMapSeed<'_, PART>
+where
+    PART: Deserialize<'de>,
+{
+    type Value = HashMap<String, PART>;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a map of part names to parts")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut parts = HashMap::new();
+
+        while let Some(name) = map.next_key::<String>()? {
+            if name == INCLUDE_KEY {
+                match map.next_value::<Vec<String>>() {
+                    Ok(includes) => self.includes.extend(includes),
+                    Err(error) => {
+                        *self.failed_part = Some(name);
+                        return Err(error);
+                    }
+                }
+                continue;
+            }
+
+            if name == ASSEMBLIES_KEY {
+                match map.next_value::<HashMap<String, Assembly>>() {
+                    Ok(assemblies) => self.assemblies.extend(assemblies),
+                    Err(error) => {
+                        *self.failed_part = Some(name);
+                        return Err(error);
+                    }
+                }
+                continue;
+            }
+
+            if self.skipped.contains(&name) {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+
+            match map.next_value::<PART>() {
+                Ok(part) => {
+                    parts.insert(name, part);
+                }
+                Err(error) => {
+                    *self.failed_part = Some(name);
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EmptyExtension;
+
+    fn parts_map(file: &str, parts: &[(&str, &str)]) -> PartsMap<EmptyExtension> {
+        let mut map = PartsMap::default();
+        for (name, path) in parts {
+            let part = serde_json::from_value(serde_json::json!({ "path": path })).unwrap();
+            map.parts.insert(name.to_string(), part);
+            map.sources.insert(name.to_string(), file.to_string());
+        }
+        map
+    }
+
+    #[test]
+    fn include_cycle_lists_the_chain() {
+        let chain = vec!["a.json".to_string(), "b.json".to_string()];
+
+        let error = check_include_cycle(&chain, "a.json", "b.json").unwrap_err();
+        assert_eq!(error.file, "b.json");
+        assert_eq!(error.message, "include cycle a.json -> b.json -> a.json");
+
+        assert!(check_include_cycle(&chain, "c.json", "b.json").is_ok());
+    }
+
+    #[test]
+    fn duplicate_part_from_another_file_is_an_error() {
+        let mut parts = parts_map("a.json", &[("Wheel", "wheel.gltf")]);
+        let included = parts_map("b.json", &[("Wheel", "other.gltf")]);
+
+        let error = merge_included(&mut parts, included, "a.json").unwrap_err();
+        assert_eq!(error.part.as_deref(), Some("Wheel"));
+        assert_eq!(
+            error.message,
+            "part Wheel is defined in both a.json and b.json"
+        );
+    }
+
+    #[test]
+    fn same_file_included_twice_is_merged_once() {
+        let mut parts = parts_map("a.json", &[("Body", "body.gltf")]);
+        merge_included(
+            &mut parts,
+            parts_map("c.json", &[("Wheel", "wheel.gltf")]),
+            "a.json",
+        )
+        .unwrap();
+        merge_included(
+            &mut parts,
+            parts_map("c.json", &[("Wheel", "wheel.gltf")]),
+            "a.json",
+        )
+        .unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts.sources["Wheel"], "c.json");
+    }
+
+    #[test]
+    fn normalize_drops_current_dir() {
+        assert_eq!(normalize(Path::new("./configs/./a.json")), "configs/a.json");
+    }
+}
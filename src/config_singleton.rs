@@ -1,57 +1,127 @@
+use crate::config_loader::{ConfigErrorQueue, ConfigLoadError};
+use crate::part_path::PartPath;
 use crate::parts_map::PartsMap;
+use crate::preload::{PreloadModels, PreloadedParts};
+use crate::progress::PartsLoadingProgress;
 use bevy::app::PreStartup;
-use bevy::asset::{Asset, AssetServer, Assets, Handle};
+use bevy::asset::{Asset, AssetId, AssetServer, Assets, Handle, LoadState};
 use bevy::prelude::{
-    Commands, FixedPreUpdate, IntoScheduleConfigs, NextState, Res, ResMut, Resource, States,
-    in_state,
+    Commands, EventWriter, FixedPreUpdate, IntoScheduleConfigs, Local, NextState, Res, ResMut,
+    Resource, States, error, in_state, warn,
 };
 use bevy::state::state::FreelyMutableState;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
 
 pub(crate) fn config_singleton<EXTENSION, STATE>(
     app: &mut bevy::prelude::App,
-    file: &'static str,
+    files: &[&'static str],
+    required_parts: &[&'static str],
+    require_data: bool,
     run_in: STATE,
     to_state: STATE,
+    failure_state: Option<STATE>,
 ) where
     for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
     STATE: States + FreelyMutableState + Clone,
 {
-    app.insert_resource(ConfigLoadTarget::<EXTENSION>::new(file))
+    app.insert_resource(ConfigLoadTarget::<EXTENSION>::new(files))
+        .insert_resource(RequiredParts::<EXTENSION>::new(
+            required_parts,
+            require_data,
+        ))
         .insert_resource(PartsMap::<EXTENSION>::default())
-        .insert_resource(MoveToState::<EXTENSION, STATE>::new(to_state))
+        .insert_resource(PartsLoadingProgress::<EXTENSION>::new(files.len()))
+        .insert_resource(MoveToState::<EXTENSION, STATE>::new(
+            to_state,
+            failure_state,
+        ))
+        .add_event::<ConfigLoadError>()
         .add_systems(PreStartup, preload_single::<EXTENSION>)
         .add_systems(
             FixedPreUpdate,
-            load_config::<EXTENSION, STATE>.run_if(in_state(run_in)),
+            (
+                report_load_errors::<EXTENSION, STATE>,
+                load_config::<EXTENSION, STATE>,
+            )
+                .run_if(in_state(run_in)),
         );
 }
 
 #[derive(Resource)]
-struct MoveToState<EXTENSION, STATE> {
-    next: STATE,
+pub(crate) struct MoveToState<EXTENSION, STATE> {
+    pub(crate) next: STATE,
+    pub(crate) failed: Option<STATE>,
     phantom_data: PhantomData<EXTENSION>,
 }
 
 impl<EXTENSION, STATE> MoveToState<EXTENSION, STATE> {
-    pub fn new(next: STATE) -> Self {
+    pub fn new(next: STATE, failed: Option<STATE>) -> Self {
         Self {
             next,
+            failed,
             phantom_data: Default::default(),
         }
     }
 }
 
+// Parts that have to be defined once every config is merged
+#[derive(Resource)]
+pub(crate) struct RequiredParts<EXTENSION> {
+    parts: Vec<&'static str>,
+    data: bool,
+    phantom_data: PhantomData<EXTENSION>,
+}
+
+impl<EXTENSION: Asset> RequiredParts<EXTENSION> {
+    pub fn new(parts: &[&'static str], data: bool) -> Self {
+        Self {
+            parts: parts.to_vec(),
+            data,
+            phantom_data: Default::default(),
+        }
+    }
+
+    // Everything wrong with the merged config in one message
+    pub(crate) fn validate(&self, parts_map: &PartsMap<EXTENSION>) -> Option<String> {
+        let mut missing: Vec<&str> = self
+            .parts
+            .iter()
+            .filter(|part| !parts_map.contains_key(**part))
+            .copied()
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+
+        let mut without_data: Vec<&str> = parts_map
+            .iter()
+            .filter(|(_, part)| self.data && part.data.is_none())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        without_data.sort_unstable();
+
+        let mut problems = vec![];
+        if !missing.is_empty() {
+            problems.push(format!("missing required parts {}", missing.join(", ")));
+        }
+        if !without_data.is_empty() {
+            problems.push(format!("parts without data {}", without_data.join(", ")));
+        }
+
+        (!problems.is_empty()).then(|| problems.join("; "))
+    }
+}
+
 #[derive(Resource)]
 struct ConfigLoadTarget<EXTENSION> {
-    path: String,
+    paths: Vec<String>,
     phantom_data: PhantomData<EXTENSION>,
 }
 
 impl<EXTENSION> ConfigLoadTarget<EXTENSION> {
-    pub fn new(path: &str) -> Self {
+    pub fn new(paths: &[&str]) -> Self {
         Self {
-            path: path.to_string(),
+            paths: paths.iter().map(|path| path.to_string()).collect(),
             phantom_data: Default::default(),
         }
     }
@@ -64,16 +134,80 @@ fn preload_single<EXTENSION>(
 ) where
     for<'de> EXTENSION: serde::Deserialize<'de> + Asset + Sync + Send + 'static,
 {
-    let parts = ConfigLoadHandle::<EXTENSION>(asset_server.load(load.path.as_str()));
+    let parts = ConfigLoadHandle::<EXTENSION>(
+        load.paths
+            .iter()
+            .map(|path| asset_server.load(path.as_str()))
+            .collect(),
+    );
     commands.insert_resource(parts);
     commands.remove_resource::<ConfigLoadTarget<EXTENSION>>();
 }
 
 #[derive(Resource)]
-pub struct ConfigLoadHandle<EXTENSION: Asset>(Handle<PartsMap<EXTENSION>>);
+pub struct ConfigLoadHandle<EXTENSION: Asset>(pub(crate) Vec<Handle<PartsMap<EXTENSION>>>);
+
+// Which config file each part in the `PartsMap` was last read from, indexed like `ConfigLoadHandle`
+#[derive(Resource)]
+pub(crate) struct ConfigSources<EXTENSION> {
+    pub(crate) owners: HashMap<String, usize>,
+    // Same for the assemblies
+    pub(crate) assembly_owners: HashMap<String, usize>,
+    // Parts that a later file overrides, by file index, so the part falls back to them if that file drops it
+    pub(crate) shadowed: HashMap<String, BTreeMap<usize, PartPath<EXTENSION>>>,
+}
+
+// Surfaces configs that failed to load instead of waiting on them forever
+#[allow(clippy::too_many_arguments)]
+fn report_load_errors<EXTENSION, STATE: States + FreelyMutableState + Clone>(
+    mut commands: Commands,
+    load: Option<Res<ConfigLoadHandle<EXTENSION>>>,
+    asset_server: Res<AssetServer>,
+    errors: Res<ConfigErrorQueue>,
+    mut progress: ResMut<PartsLoadingProgress<EXTENSION>>,
+    next_state_res: Res<MoveToState<EXTENSION, STATE>>,
+    mut error_events: EventWriter<ConfigLoadError>,
+    mut next_state: ResMut<NextState<STATE>>,
+    mut reported: Local<HashSet<AssetId<PartsMap<EXTENSION>>>>,
+) where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+{
+    let Some(load) = load else {
+        return;
+    };
+
+    for handle in load.0.iter() {
+        if reported.contains(&handle.id()) {
+            continue;
+        }
+
+        let LoadState::Failed(load_error) = asset_server.load_state(handle.id()) else {
+            continue;
+        };
+        reported.insert(handle.id());
+
+        let file = asset_server
+            .get_path(handle.id())
+            .map(|path| path.path().to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let error = errors
+            .take(&file)
+            .unwrap_or_else(|| ConfigLoadError::new(&file, load_error));
+
+        error!("{}", error);
+        progress.fail(&error.file, error.message.clone());
+        commands.insert_resource(error.clone());
+        error_events.write(error);
+
+        if let Some(failed) = &next_state_res.failed {
+            next_state.set(failed.clone());
+        }
+    }
+}
 
 // Assumed the file is already loaded, this should work for most scenarios
-#[allow(private_interfaces)]
+#[allow(private_interfaces, clippy::too_many_arguments)]
 pub fn load_config<EXTENSION, STATE: States + FreelyMutableState + Clone>(
     mut commands: Commands,
     mut parts_map: ResMut<PartsMap<EXTENSION>>,
@@ -81,13 +215,162 @@ pub fn load_config<EXTENSION, STATE: States + FreelyMutableState + Clone>(
     next_state_res: Res<MoveToState<EXTENSION, STATE>>,
     mut config: ResMut<Assets<PartsMap<EXTENSION>>>,
     mut next_state: ResMut<NextState<STATE>>,
+    preload: Option<Res<PreloadModels<EXTENSION>>>,
+    asset_server: Res<AssetServer>,
+    mut progress: ResMut<PartsLoadingProgress<EXTENSION>>,
+    required: Res<RequiredParts<EXTENSION>>,
+    mut error_events: EventWriter<ConfigLoadError>,
 ) where
     for<'de> EXTENSION: serde::Deserialize<'de> + Asset + Sync + Send + 'static,
 {
-    if let Some(asset) = load.and_then(|res| config.remove(res.0.id())) {
-        commands.remove_resource::<ConfigLoadTarget<EXTENSION>>();
-        parts_map.0 = asset.0;
+    let Some(load) = load else {
+        return;
+    };
 
-        next_state.set(next_state_res.next.clone());
+    // Already merged, any later change to the configs is up to hot reloading
+    if progress.config_parsed {
+        return;
+    }
+
+    let loaded = load
+        .0
+        .iter()
+        .filter(|handle| config.contains(handle.id()))
+        .count();
+
+    // Wait until every config is available so the merged map is never partial
+    if loaded < load.0.len() {
+        progress.configs_loaded = loaded;
+        return;
+    }
+
+    progress.configs_loaded = loaded;
+    progress.config_parsed = true;
+
+    commands.remove_resource::<ConfigLoadTarget<EXTENSION>>();
+
+    let files: Vec<String> = load
+        .0
+        .iter()
+        .filter_map(|handle| asset_server.get_path(handle.id()))
+        .map(|path| path.path().to_string_lossy().to_string())
+        .collect();
+
+    let mut owners = HashMap::new();
+    let mut assembly_owners = HashMap::new();
+    let mut shadowed: HashMap<String, BTreeMap<usize, PartPath<EXTENSION>>> = HashMap::new();
+    for (index, handle) in load.0.iter().enumerate() {
+        let Some(asset) = config.remove(handle.id()) else {
+            continue;
+        };
+
+        for (name, assembly) in asset.assemblies {
+            parts_map.assemblies.insert(name.clone(), assembly);
+            assembly_owners.insert(name, index);
+        }
+        for (name, part) in asset.parts {
+            if let Some(old) = parts_map.parts.insert(name.clone(), part) {
+                warn!(
+                    "part {} is defined in more than one config, using the last one",
+                    name
+                );
+                shadowed
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(owners[&name], old);
+            }
+            owners.insert(name, index);
+        }
+    }
+
+    commands.insert_resource(ConfigSources::<EXTENSION> {
+        owners,
+        assembly_owners,
+        shadowed,
+    });
+
+    if let Some(problems) = required.validate(&parts_map) {
+        let error = ConfigLoadError::new(&files.join(", "), problems);
+
+        error!("{}", error);
+        progress.validation_error = Some(error.message.clone());
+        commands.insert_resource(error.clone());
+        error_events.write(error);
+
+        if let Some(failed) = &next_state_res.failed {
+            next_state.set(failed.clone());
+        }
+        return;
+    }
+
+    // The state is moved by `wait_for_models` once everything is loaded
+    if preload.is_some() {
+        commands.insert_resource(PreloadedParts::<EXTENSION>::new(
+            parts_map.values().map(|part| &part.path),
+            &asset_server,
+        ));
+        return;
+    }
+
+    next_state.set(next_state_res.next.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::reflect::TypePath;
+    use serde::Deserialize;
+
+    #[derive(Asset, TypePath, Deserialize)]
+    struct Speed {
+        #[allow(dead_code)]
+        speed: f32,
+    }
+
+    fn parts_map(parts: serde_json::Value) -> PartsMap<Speed> {
+        PartsMap {
+            parts: serde_json::from_value(parts).unwrap(),
+            sources: HashMap::new(),
+            assemblies: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn valid_config_has_no_problems() {
+        let parts = parts_map(serde_json::json!({
+            "Wheel": { "path": "wheel.gltf", "data": { "speed": 1.0 } },
+        }));
+
+        assert_eq!(RequiredParts::new(&["Wheel"], true).validate(&parts), None);
+        assert_eq!(RequiredParts::new(&[], false).validate(&parts), None);
+    }
+
+    #[test]
+    fn missing_parts_are_sorted_and_listed_once() {
+        let parts = parts_map(serde_json::json!({ "Wheel": { "path": "wheel.gltf" } }));
+
+        let problems =
+            RequiredParts::new(&["Turret", "Wheel", "Body", "Turret"], false).validate(&parts);
+        assert_eq!(
+            problems.as_deref(),
+            Some("missing required parts Body, Turret")
+        );
+    }
+
+    #[test]
+    fn parts_without_data_are_only_checked_when_required() {
+        let parts = parts_map(serde_json::json!({
+            "Wheel": { "path": "wheel.gltf" },
+            "Body": { "path": "body.gltf" },
+            "Turret": { "path": "turret.gltf", "data": { "speed": 1.0 } },
+        }));
+
+        assert_eq!(RequiredParts::new(&[], false).validate(&parts), None);
+        assert_eq!(
+            RequiredParts::new(&["Gun"], true)
+                .validate(&parts)
+                .as_deref(),
+            Some("missing required parts Gun; parts without data Body, Wheel")
+        );
     }
 }
@@ -1,77 +1,644 @@
-use bevy::pbr::{ExtendedMaterial, MaterialExtension};
+use crate::SpawnedPart;
+use crate::glob::glob_match;
+use crate::part_ready::MaterialPending;
+use bevy::app::{App, PostUpdate, Update};
+use bevy::asset::{AssetEvent, AssetId, AssetServer, Handle, LoadState};
+use bevy::gltf::GltfMaterialName;
+use bevy::pbr::{ExtendedMaterial, MaterialExtension, MaterialPlugin};
 use bevy::prelude::{
-    Assets, Children, Commands, Component, Entity, MeshMaterial3d, Query, ResMut, StandardMaterial,
-    Trigger,
+    Assets, ChildOf, Children, Commands, Component, Entity, EntityRef, EntityWorldMut, Event,
+    EventReader, EventWriter, MeshMaterial3d, Name, Query, Res, ResMut, Resource, StandardMaterial,
+    Trigger, warn,
 };
 use bevy::scene::SceneInstanceReady;
+use std::any::type_name;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+// Extension type given to `RuntimeGlftLoaderPlugin::register_material_extension`
+#[derive(Clone, Copy)]
+pub(crate) struct RegisteredMaterialExtension {
+    build: fn(&mut App, bool),
+    finish: fn(&App),
+}
+
+impl RegisteredMaterialExtension {
+    pub(crate) fn new<MATERIAL: MaterialExtension + Clone>() -> Self
+    where
+        MATERIAL::Data: PartialEq + Eq + Hash + Clone,
+    {
+        Self {
+            build: material_extension::<MATERIAL>,
+            finish: check_material_assets::<MATERIAL>,
+        }
+    }
+
+    // Meshes with the same base material and an equal extension value share one extended material
+    pub(crate) fn shared<MATERIAL: MaterialExtension + Clone + PartialEq + Hash>() -> Self
+    where
+        MATERIAL::Data: PartialEq + Eq + Hash + Clone,
+    {
+        Self {
+            build: shared_material_extension::<MATERIAL>,
+            finish: check_material_assets::<MATERIAL>,
+        }
+    }
+
+    pub(crate) fn build(&self, app: &mut App, add_material_plugin: bool) {
+        (self.build)(app, add_material_plugin);
+    }
+
+    pub(crate) fn finish(&self, app: &App) {
+        (self.finish)(app);
+    }
+}
+
+fn material_extension<MATERIAL: MaterialExtension + Clone>(app: &mut App, add_material_plugin: bool)
+where
+    MATERIAL::Data: PartialEq + Eq + Hash + Clone,
+{
+    add_material_extension::<MATERIAL>(app, add_material_plugin, None);
+}
+
+fn shared_material_extension<MATERIAL: MaterialExtension + Clone + PartialEq + Hash>(
+    app: &mut App,
+    add_material_plugin: bool,
+) where
+    MATERIAL::Data: PartialEq + Eq + Hash + Clone,
+{
+    add_material_extension::<MATERIAL>(
+        app,
+        add_material_plugin,
+        Some(SharedExtension {
+            hash: extension_hash::<MATERIAL>,
+            eq: MATERIAL::eq,
+        }),
+    );
+}
+
+fn extension_hash<MATERIAL: Hash>(extension: &MATERIAL) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    extension.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Systems applying `MATERIAL` to parts loaded with `PartLoader::extend_material`, added once per extension type
+fn add_material_extension<MATERIAL: MaterialExtension + Clone>(
+    app: &mut App,
+    add_material_plugin: bool,
+    shared: Option<SharedExtension<MATERIAL>>,
+) where
+    MATERIAL::Data: PartialEq + Eq + Hash + Clone,
+{
+    if add_material_plugin
+        && !app.is_plugin_added::<MaterialPlugin<ExtendedMaterial<StandardMaterial, MATERIAL>>>()
+    {
+        app.add_plugins(MaterialPlugin::<ExtendedMaterial<StandardMaterial, MATERIAL>>::default());
+    }
+
+    if app
+        .world()
+        .contains_resource::<ExtendedMaterialCache<MATERIAL>>()
+    {
+        return;
+    }
+
+    app.insert_resource(ExtendedMaterialCache::<MATERIAL> {
+        materials: HashMap::new(),
+        shared,
+    })
+    .add_event::<MaterialExtensionError>()
+    .add_observer(replace_material::<MATERIAL>)
+    .add_systems(Update, retry_material_extensions::<MATERIAL>)
+    .add_systems(PostUpdate, evict_dropped_materials::<MATERIAL>);
+}
+
+// Without the material assets the extension would fail the first time a part scene is spawned
+fn check_material_assets<MATERIAL: MaterialExtension>(app: &App) {
+    if !app
+        .world()
+        .contains_resource::<Assets<ExtendedMaterial<StandardMaterial, MATERIAL>>>()
+    {
+        let material = type_name::<ExtendedMaterial<StandardMaterial, MATERIAL>>();
+        panic!(
+            "unable to find Assets<{}>, add MaterialPlugin::<{}> or call RuntimeGlftLoaderPlugin::add_material_plugins",
+            material, material
+        );
+    }
+}
 
 #[derive(Component)]
 pub struct WithMaterialExtension<MATERIAL>(pub MATERIAL);
 
+// Picks meshes of a part for its material extension, set with the `PartLoader::extend_material_*` methods
+#[derive(Clone)]
+pub(crate) enum MaterialFilter {
+    // Glob matched against the `Name` of the mesh or any of its parents below the part entity
+    Node(String),
+    // Glob matched against the `GltfMaterialName` of the mesh
+    Material(String),
+    Predicate(Arc<dyn Fn(EntityRef) -> bool + Send + Sync>),
+}
+
+// Only meshes matching any of the filters get the extension, every mesh does without this component
+#[derive(Component)]
+pub struct MaterialExtensionFilter<MATERIAL> {
+    filters: Vec<MaterialFilter>,
+    phantom: PhantomData<MATERIAL>,
+}
+
+impl<MATERIAL> MaterialExtensionFilter<MATERIAL> {
+    pub(crate) fn new(filters: Vec<MaterialFilter>) -> Self {
+        Self {
+            filters,
+            phantom: PhantomData,
+        }
+    }
+
+    fn matches(&self, part: Entity, mesh: EntityRef, entities: &Query<EntityRef>) -> bool {
+        self.filters.iter().any(|filter| match filter {
+            MaterialFilter::Node(pattern) => {
+                let mut current = Some(mesh);
+                while let Some(entity) = current.filter(|entity| entity.id() != part) {
+                    if entity
+                        .get::<Name>()
+                        .is_some_and(|name| glob_match(pattern, name.as_str()))
+                    {
+                        return true;
+                    }
+                    current = entity
+                        .get::<ChildOf>()
+                        .and_then(|child_of| entities.get(child_of.parent()).ok());
+                }
+                false
+            }
+            MaterialFilter::Material(pattern) => mesh
+                .get::<GltfMaterialName>()
+                .is_some_and(|name| glob_match(pattern, &name.0)),
+            MaterialFilter::Predicate(predicate) => predicate(mesh),
+        })
+    }
+}
+
+type ExtendedMaterialHandle<MATERIAL> = Handle<ExtendedMaterial<StandardMaterial, MATERIAL>>;
+
+// Base material and extension hash
+type MaterialKey = (AssetId<StandardMaterial>, u64);
+
+// How the extension values of `register_shared_material_extension` are keyed and compared
+struct SharedExtension<MATERIAL> {
+    hash: fn(&MATERIAL) -> u64,
+    eq: fn(&MATERIAL, &MATERIAL) -> bool,
+}
+
+/// Extended materials already created for a base material and extension value, shared between every mesh using them
+/// Only filled for extensions given to `register_shared_material_extension`, with `register_material_extension`
+/// every mesh gets its own material and this stays empty
+/// Handles are weak so the materials are still dropped once no mesh uses them
+#[derive(Resource)]
+pub struct ExtendedMaterialCache<MATERIAL: MaterialExtension> {
+    // Keyed by base material and extension hash, values with the same hash are told apart with `eq`
+    materials: HashMap<MaterialKey, Vec<(MATERIAL, ExtendedMaterialHandle<MATERIAL>)>>,
+    shared: Option<SharedExtension<MATERIAL>>,
+}
+
+impl<MATERIAL: MaterialExtension> ExtendedMaterialCache<MATERIAL> {
+    pub fn len(&self) -> usize {
+        self.materials.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+}
+
+pub fn evict_dropped_materials<MATERIAL: MaterialExtension>(
+    mut events: EventReader<AssetEvent<ExtendedMaterial<StandardMaterial, MATERIAL>>>,
+    mut cache: ResMut<ExtendedMaterialCache<MATERIAL>>,
+) {
+    for event in events.read() {
+        if let AssetEvent::Removed { id } = event {
+            cache.materials.retain(|_, shared| {
+                shared.retain(|(_, handle)| handle.id() != *id);
+                !shared.is_empty()
+            });
+        }
+    }
+}
+
+/// Sent when a mesh of a part could not get its extended material because its base material is missing
+/// The mesh keeps its `StandardMaterial`
+#[derive(Event, Debug, Clone)]
+pub struct MaterialExtensionError {
+    // Mesh entity whose material was not replaced
+    pub entity: Entity,
+    // Part entity the extension was requested on
+    pub part: Entity,
+    // Empty when the part was not loaded through a `PartsMap`
+    pub part_name: String,
+    pub message: String,
+}
+
+impl Display for MaterialExtensionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unable to extend material of {} in part {} ({}): {}",
+            self.entity, &self.part_name, self.part, &self.message
+        )
+    }
+}
+
+impl Error for MaterialExtensionError {}
+
+// Mesh whose base material was still loading when the scene was spawned, retried every frame
+#[derive(Component)]
+pub struct PendingMaterialExtension<MATERIAL> {
+    part: Entity,
+    phantom: PhantomData<MATERIAL>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn replace_material<MATERIAL: MaterialExtension + Clone>(
     trigger: Trigger<SceneInstanceReady>,
     mut commands: Commands,
-    material_extension_query: Query<&WithMaterialExtension<MATERIAL>>,
-    query_children: Query<(Option<&MeshMaterial3d<StandardMaterial>>, Option<&Children>)>,
+    material_extension_query: Query<(
+        &WithMaterialExtension<MATERIAL>,
+        Option<&MaterialExtensionFilter<MATERIAL>>,
+    )>,
+    children: Query<&Children>,
+    mesh_materials: Query<&MeshMaterial3d<StandardMaterial>>,
+    entities: Query<EntityRef>,
     mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, MATERIAL>>>,
-    mut std_materials: ResMut<Assets<StandardMaterial>>,
+    std_materials: Res<Assets<StandardMaterial>>,
+    mut cache: ResMut<ExtendedMaterialCache<MATERIAL>>,
 ) {
     let target = trigger.target();
 
-    let Ok(with_material_extension) = material_extension_query.get(target) else {
+    // Kept on the entity so the extension is applied again when the scene is replaced
+    let Ok((WithMaterialExtension(material_extension), filter)) =
+        material_extension_query.get(target)
+    else {
         return;
     };
 
-    commands
-        .entity(target)
-        .remove::<WithMaterialExtension<MATERIAL>>();
-    let WithMaterialExtension(material_extension) = with_material_extension;
-
-    process_children(
-        target,
-        material_extension,
-        &mut commands,
-        &query_children,
-        &mut materials,
-        &mut std_materials,
-    );
+    for entity in std::iter::once(target).chain(children.iter_descendants(target)) {
+        let Ok(mat) = mesh_materials.get(entity) else {
+            continue;
+        };
+
+        if let Some(filter) = filter {
+            let matches = entities
+                .get(entity)
+                .is_ok_and(|mesh| filter.matches(target, mesh, &entities));
+            if !matches {
+                continue;
+            }
+        }
+
+        match extended_material(
+            &mat.0,
+            material_extension,
+            &mut materials,
+            &std_materials,
+            &mut cache,
+        ) {
+            Some(handle) => swap_material(&mut commands, entity, handle),
+            // Checked again by `retry_material_extensions` until the base material is loaded or fails
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(PendingMaterialExtension::<MATERIAL> {
+                        part: target,
+                        phantom: PhantomData,
+                    })
+                    .entry::<MaterialPending>()
+                    .and_modify(|mut pending| pending.0 += 1)
+                    .or_insert(MaterialPending(1));
+            }
+        }
+    }
 }
 
-fn process_children<MATERIAL: MaterialExtension + Clone>(
-    entity: Entity,
-    material_extension: &MATERIAL,
-    commands: &mut Commands,
-    query_children: &Query<(Option<&MeshMaterial3d<StandardMaterial>>, Option<&Children>)>,
-    materials: &mut ResMut<Assets<ExtendedMaterial<StandardMaterial, MATERIAL>>>,
-    std_materials: &mut ResMut<Assets<StandardMaterial>>,
+#[allow(clippy::too_many_arguments)]
+pub fn retry_material_extensions<MATERIAL: MaterialExtension + Clone>(
+    mut commands: Commands,
+    pending: Query<(
+        Entity,
+        &MeshMaterial3d<StandardMaterial>,
+        &PendingMaterialExtension<MATERIAL>,
+    )>,
+    material_extension_query: Query<(&WithMaterialExtension<MATERIAL>, Option<&SpawnedPart>)>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, MATERIAL>>>,
+    std_materials: Res<Assets<StandardMaterial>>,
+    mut cache: ResMut<ExtendedMaterialCache<MATERIAL>>,
+    mut errors: EventWriter<MaterialExtensionError>,
 ) {
-    if let Ok((mat, children)) = query_children.get(entity) {
-        // Process material
-        if let Some(mat) = mat {
-            let std = std_materials.get(&mat.0).unwrap();
-
-            let extended = ExtendedMaterial {
-                base: std.clone(),
-                extension: material_extension.clone(),
-            };
-
-            commands
-                .entity(entity)
-                .remove::<MeshMaterial3d<StandardMaterial>>()
-                .insert(MeshMaterial3d(materials.add(extended)));
+    for (entity, mat, PendingMaterialExtension { part, .. }) in pending.iter() {
+        // The part was despawned or replaced without an extension
+        let Ok((WithMaterialExtension(material_extension), spawned_part)) =
+            material_extension_query.get(*part)
+        else {
+            remove_pending::<MATERIAL>(&mut commands, entity);
+            continue;
+        };
+
+        if let Some(handle) = extended_material(
+            &mat.0,
+            material_extension,
+            &mut materials,
+            &std_materials,
+            &mut cache,
+        ) {
+            remove_pending::<MATERIAL>(&mut commands, entity);
+            swap_material(&mut commands, entity, handle);
+            continue;
         }
 
-        if let Some(children) = children {
-            for child in children.iter() {
-                process_children(
-                    *child,
-                    material_extension,
-                    commands,
-                    query_children,
-                    materials,
-                    std_materials,
-                );
+        let message = match asset_server.get_load_state(mat.0.id()) {
+            Some(LoadState::Loading) => continue,
+            Some(LoadState::Failed(load_error)) => {
+                format!("base material failed to load: {}", load_error)
             }
+            _ => format!("base material {} is not loaded", mat.0.id()),
+        };
+
+        remove_pending::<MATERIAL>(&mut commands, entity);
+
+        let error = MaterialExtensionError {
+            entity,
+            part: *part,
+            part_name: spawned_part
+                .map(|spawned_part| spawned_part.name.clone())
+                .unwrap_or_default(),
+            message,
+        };
+        warn!("{}", error);
+        errors.write(error);
+    }
+}
+
+// Extended material for the base material, `None` while the base material is not available
+fn extended_material<MATERIAL: MaterialExtension + Clone>(
+    base: &Handle<StandardMaterial>,
+    material_extension: &MATERIAL,
+    materials: &mut Assets<ExtendedMaterial<StandardMaterial, MATERIAL>>,
+    std_materials: &Assets<StandardMaterial>,
+    cache: &mut ExtendedMaterialCache<MATERIAL>,
+) -> Option<ExtendedMaterialHandle<MATERIAL>> {
+    let shared = cache
+        .shared
+        .as_ref()
+        .map(|shared| ((base.id(), (shared.hash)(material_extension)), shared.eq));
+
+    if let Some((key, eq)) = shared {
+        let cached = cache
+            .materials
+            .get(&key)
+            .and_then(|cached| {
+                cached
+                    .iter()
+                    .find(|(extension, _)| eq(extension, material_extension))
+            })
+            .and_then(|(_, handle)| materials.get_strong_handle(handle.id()));
+        if cached.is_some() {
+            return cached;
         }
     }
+
+    let std = std_materials.get(base)?;
+
+    let extended = ExtendedMaterial {
+        base: std.clone(),
+        extension: material_extension.clone(),
+    };
+
+    let handle = materials.add(extended);
+    if let Some((key, _)) = shared {
+        cache
+            .materials
+            .entry(key)
+            .or_default()
+            .push((material_extension.clone(), handle.clone_weak()));
+    }
+    Some(handle)
+}
+
+fn swap_material<MATERIAL: MaterialExtension>(
+    commands: &mut Commands,
+    entity: Entity,
+    handle: ExtendedMaterialHandle<MATERIAL>,
+) {
+    commands
+        .entity(entity)
+        .remove::<MeshMaterial3d<StandardMaterial>>()
+        .insert(MeshMaterial3d(handle));
+}
+
+fn remove_pending<MATERIAL: MaterialExtension>(commands: &mut Commands, entity: Entity) {
+    commands
+        .entity(entity)
+        .remove::<PendingMaterialExtension<MATERIAL>>()
+        .queue(|mut entity: EntityWorldMut| {
+            let done = entity
+                .get_mut::<MaterialPending>()
+                .is_some_and(|mut pending| {
+                    pending.0 -= 1;
+                    pending.0 == 0
+                });
+            if done {
+                entity.remove::<MaterialPending>();
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::MinimalPlugins;
+    use bevy::asset::{Asset, AssetApp, AssetPlugin};
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::Reflect;
+    use bevy::render::render_resource::AsBindGroup;
+
+    #[derive(Asset, AsBindGroup, Reflect, Debug, Clone, PartialEq, Hash)]
+    struct Steps {
+        #[uniform(100)]
+        steps: u32,
+    }
+
+    impl MaterialExtension for Steps {}
+
+    fn app(shared: bool) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<StandardMaterial>()
+            .init_asset::<ExtendedMaterial<StandardMaterial, Steps>>()
+            .insert_resource(ExtendedMaterialCache::<Steps> {
+                materials: HashMap::new(),
+                shared: shared.then_some(SharedExtension {
+                    hash: extension_hash::<Steps>,
+                    eq: Steps::eq,
+                }),
+            })
+            .add_systems(PostUpdate, evict_dropped_materials::<Steps>);
+        app
+    }
+
+    fn add_base(app: &mut App) -> Handle<StandardMaterial> {
+        app.world_mut()
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial::default())
+    }
+
+    fn extend(
+        app: &mut App,
+        base: &Handle<StandardMaterial>,
+        steps: u32,
+    ) -> ExtendedMaterialHandle<Steps> {
+        let base = base.clone();
+        app.world_mut()
+            .run_system_once(
+                move |mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, Steps>>>,
+                      std_materials: Res<Assets<StandardMaterial>>,
+                      mut cache: ResMut<ExtendedMaterialCache<Steps>>| {
+                    extended_material(
+                        &base,
+                        &Steps { steps },
+                        &mut materials,
+                        &std_materials,
+                        &mut cache,
+                    )
+                    .unwrap()
+                },
+            )
+            .unwrap()
+    }
+
+    fn matches(app: &mut App, filters: Vec<MaterialFilter>, part: Entity, mesh: Entity) -> bool {
+        let filter = MaterialExtensionFilter::<Steps>::new(filters);
+        app.world_mut()
+            .run_system_once(move |entities: Query<EntityRef>| {
+                filter.matches(part, entities.get(mesh).unwrap(), &entities)
+            })
+            .unwrap()
+    }
+
+    // Car_Root > Wheel_Part > Hub > Mesh0, with the part entity being Wheel_Part
+    fn hierarchy(app: &mut App) -> (Entity, Entity) {
+        let world = app.world_mut();
+        let root = world.spawn(Name::new("Car_Root")).id();
+        let part = world.spawn((Name::new("Wheel_Part"), ChildOf(root))).id();
+        let hub = world.spawn((Name::new("Hub"), ChildOf(part))).id();
+        let mesh = world
+            .spawn((
+                Name::new("Mesh0"),
+                GltfMaterialName("Rubber".to_string()),
+                ChildOf(hub),
+            ))
+            .id();
+        (part, mesh)
+    }
+
+    fn node(pattern: &str) -> MaterialFilter {
+        MaterialFilter::Node(pattern.to_string())
+    }
+
+    fn material(pattern: &str) -> MaterialFilter {
+        MaterialFilter::Material(pattern.to_string())
+    }
+
+    #[test]
+    fn node_filter_walks_up_to_the_part() {
+        let mut app = app(false);
+        let (part, mesh) = hierarchy(&mut app);
+
+        assert!(matches(&mut app, vec![node("Mesh*")], part, mesh));
+        assert!(matches(&mut app, vec![node("Hub")], part, mesh));
+        assert!(!matches(&mut app, vec![node("Wheel_*")], part, mesh));
+        assert!(!matches(&mut app, vec![node("Car_*")], part, mesh));
+    }
+
+    #[test]
+    fn material_filter_matches_the_gltf_material_name() {
+        let mut app = app(false);
+        let (part, mesh) = hierarchy(&mut app);
+        let unnamed = app.world_mut().spawn(ChildOf(part)).id();
+
+        assert!(matches(&mut app, vec![material("Rub*")], part, mesh));
+        assert!(!matches(&mut app, vec![material("Metal")], part, mesh));
+        assert!(!matches(&mut app, vec![material("*")], part, unnamed));
+    }
+
+    #[test]
+    fn predicate_filter_gets_the_mesh() {
+        let mut app = app(false);
+        let (part, mesh) = hierarchy(&mut app);
+        let is_mesh0 = MaterialFilter::Predicate(Arc::new(|mesh: EntityRef| {
+            mesh.get::<Name>()
+                .is_some_and(|name| name.as_str() == "Mesh0")
+        }));
+
+        assert!(matches(&mut app, vec![is_mesh0.clone()], part, mesh));
+        assert!(!matches(&mut app, vec![is_mesh0], part, part));
+    }
+
+    #[test]
+    fn any_filter_can_match() {
+        let mut app = app(false);
+        let (part, mesh) = hierarchy(&mut app);
+
+        let hub = vec![material("Metal"), node("Hub")];
+        let rim = vec![material("Metal"), node("Rim")];
+
+        assert!(matches(&mut app, hub, part, mesh));
+        assert!(!matches(&mut app, rim, part, mesh));
+    }
+
+    fn cache(app: &App) -> &ExtendedMaterialCache<Steps> {
+        app.world().resource::<ExtendedMaterialCache<Steps>>()
+    }
+
+    #[test]
+    fn equal_extensions_share_one_material() {
+        let mut app = app(true);
+        let base = add_base(&mut app);
+        let other_base = add_base(&mut app);
+
+        let first = extend(&mut app, &base, 4);
+        assert_eq!(extend(&mut app, &base, 4), first);
+        assert_ne!(extend(&mut app, &base, 8), first);
+        assert_ne!(extend(&mut app, &other_base, 4), first);
+        assert_eq!(cache(&app).len(), 3);
+    }
+
+    #[test]
+    fn materials_are_not_shared_by_default() {
+        let mut app = app(false);
+        let base = add_base(&mut app);
+
+        let first = extend(&mut app, &base, 4);
+        assert_ne!(extend(&mut app, &base, 4), first);
+        assert!(cache(&app).is_empty());
+    }
+
+    #[test]
+    fn dropped_material_is_evicted() {
+        let mut app = app(true);
+        let base = add_base(&mut app);
+
+        let handle = extend(&mut app, &base, 4);
+        app.update();
+        assert_eq!(cache(&app).len(), 1);
+
+        drop(handle);
+        app.update();
+        app.update();
+        assert!(cache(&app).is_empty());
+    }
 }
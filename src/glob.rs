@@ -0,0 +1,59 @@
+// Matches `text` against a pattern where `*` is any run of characters and `?` is a single character
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Last `*` seen and the text position it was tried at, to backtrack into
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn literal_and_single_character() {
+        assert!(glob_match("parts.json", "parts.json"));
+        assert!(!glob_match("parts.json", "parts.ron"));
+        assert!(glob_match("part?.json", "part1.json"));
+        assert!(!glob_match("part?.json", "part.json"));
+    }
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(glob_match("*.json", "parts.json"));
+        assert!(glob_match("*.json", ".json"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("**", "anything"));
+        assert!(!glob_match("*.json", "parts.ron"));
+    }
+
+    #[test]
+    fn star_backtracks() {
+        // The first `a` tried for `*a` is not the one that lets the rest match
+        assert!(glob_match("*ab", "aaab"));
+        assert!(glob_match("*a*b?", "xaxxbyb1"));
+        assert!(glob_match("wheel_*_*.json", "wheel_front_left.json"));
+        assert!(!glob_match("*ab", "aaba"));
+        assert!(!glob_match("a*b*c", "aXbXd"));
+    }
+}
@@ -0,0 +1,324 @@
+use crate::config_loader::{ConfigErrorQueue, ConfigLoadError};
+use crate::config_singleton::{ConfigLoadHandle, ConfigSources, RequiredParts};
+use crate::part_path::PartPath;
+use crate::parts_map::PartsMap;
+use crate::spawned_part::PartInstances;
+use bevy::asset::{Asset, AssetEvent, AssetLoadFailedEvent, AssetServer, Assets};
+use bevy::prelude::{
+    Commands, Event, EventReader, EventWriter, IntoScheduleConfigs, Res, ResMut, Resource, Update,
+    error, resource_exists,
+};
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+pub(crate) fn hot_reload<EXTENSION>(app: &mut bevy::prelude::App, respawn: bool)
+where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+{
+    app.add_event::<PartsMapChanged<EXTENSION>>().add_systems(
+        Update,
+        (
+            reload_config::<EXTENSION>,
+            report_reload_errors::<EXTENSION>,
+        )
+            .run_if(resource_exists::<ConfigSources<EXTENSION>>),
+    );
+
+    if respawn {
+        app.insert_resource(RespawnOnReload::<EXTENSION>(Default::default()));
+    }
+}
+
+/// Sent after a config file changed on disk and `PartsMap<EXTENSION>` was rebuilt from it
+#[derive(Event)]
+pub struct PartsMapChanged<EXTENSION> {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    // Parts whose entry in the changed file is different from before
+    pub changed: Vec<String>,
+    phantom_data: PhantomData<EXTENSION>,
+}
+
+impl<EXTENSION> Default for PartsMapChanged<EXTENSION> {
+    fn default() -> Self {
+        Self {
+            added: vec![],
+            removed: vec![],
+            changed: vec![],
+            phantom_data: Default::default(),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct RespawnOnReload<EXTENSION>(PhantomData<EXTENSION>);
+
+// The initially loaded configs are removed from `Assets` so any config asset that shows up again
+// is the asset server reloading a modified file
+#[allow(clippy::too_many_arguments)]
+fn reload_config<EXTENSION>(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<PartsMap<EXTENSION>>>,
+    mut changed_events: EventWriter<PartsMapChanged<EXTENSION>>,
+    load: Res<ConfigLoadHandle<EXTENSION>>,
+    mut sources: ResMut<ConfigSources<EXTENSION>>,
+    mut parts_map: ResMut<PartsMap<EXTENSION>>,
+    mut config: ResMut<Assets<PartsMap<EXTENSION>>>,
+    respawn: Option<Res<RespawnOnReload<EXTENSION>>>,
+    instances: PartInstances,
+    asset_server: Res<AssetServer>,
+    required: Res<RequiredParts<EXTENSION>>,
+    mut error_events: EventWriter<ConfigLoadError>,
+) where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+{
+    for event in asset_events.read() {
+        let (AssetEvent::Added { id } | AssetEvent::Modified { id }) = event else {
+            continue;
+        };
+        let Some(index) = load.0.iter().position(|handle| handle.id() == *id) else {
+            continue;
+        };
+        let Some(asset) = config.remove(*id) else {
+            continue;
+        };
+
+        let mut changed = PartsMapChanged::<EXTENSION>::default();
+        // Parts that now spawn something else, along with the file they were spawned from
+        let mut moved = vec![];
+
+        let previous: Vec<String> = sources
+            .owners
+            .iter()
+            .filter(|(_, owner)| **owner == index)
+            .map(|(name, _)| name.clone())
+            .collect();
+        // The parts this file overrode before are read again below
+        for shadowed in sources.shadowed.values_mut() {
+            shadowed.remove(&index);
+        }
+
+        for name in previous {
+            if asset.parts.contains_key(&name) {
+                continue;
+            }
+
+            // The last earlier file that still defines the part takes over
+            let fallback = sources
+                .shadowed
+                .get_mut(&name)
+                .and_then(|shadowed| shadowed.pop_last());
+            match fallback {
+                Some((owner, part)) => {
+                    sources.owners.insert(name.clone(), owner);
+                    replace_part(&mut parts_map, &name, part, &mut changed, &mut moved);
+                }
+                None => {
+                    sources.owners.remove(&name);
+                    parts_map.parts.remove(&name);
+                    changed.removed.push(name);
+                }
+            }
+        }
+
+        sources.assembly_owners.retain(|name, owner| {
+            let removed = *owner == index && !asset.assemblies.contains_key(name);
+            if removed {
+                parts_map.assemblies.remove(name);
+            }
+            !removed
+        });
+        for (name, assembly) in asset.assemblies {
+            if sources
+                .assembly_owners
+                .get(&name)
+                .is_some_and(|owner| *owner > index)
+            {
+                continue;
+            }
+
+            parts_map.assemblies.insert(name.clone(), assembly);
+            sources.assembly_owners.insert(name, index);
+        }
+
+        for (name, part) in asset.parts {
+            // Keep the last file wins rule from the initial load
+            let owner = sources.owners.get(&name).copied();
+            if owner.is_some_and(|owner| owner > index) {
+                sources
+                    .shadowed
+                    .entry(name)
+                    .or_default()
+                    .insert(index, part);
+                continue;
+            }
+
+            let old = replace_part(&mut parts_map, &name, part, &mut changed, &mut moved);
+            if let (Some(owner), Some(old)) = (owner.filter(|owner| *owner != index), old) {
+                sources
+                    .shadowed
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(owner, old);
+            }
+            sources.owners.insert(name, index);
+        }
+
+        if respawn.is_some() {
+            for (name, old_path) in moved {
+                respawn_part(
+                    &name,
+                    &old_path,
+                    &parts_map,
+                    &instances,
+                    &mut commands,
+                    &asset_server,
+                );
+            }
+        }
+
+        changed_events.write(changed);
+
+        // The reloaded parts are kept, the error only points out what the file is missing
+        if let Some(problems) = required.validate(&parts_map) {
+            let file = asset_server
+                .get_path(*id)
+                .map(|path| path.path().to_string_lossy().to_string())
+                .unwrap_or_default();
+            let error = ConfigLoadError::new(&file, problems);
+
+            error!("{}", error);
+            commands.insert_resource(error.clone());
+            error_events.write(error);
+        }
+    }
+}
+
+// A config that fails to reload leaves the `PartsMap` as it was, the failure is only reported
+fn report_reload_errors<EXTENSION>(
+    mut commands: Commands,
+    mut failed_events: EventReader<AssetLoadFailedEvent<PartsMap<EXTENSION>>>,
+    load: Res<ConfigLoadHandle<EXTENSION>>,
+    errors: Res<ConfigErrorQueue>,
+    mut error_events: EventWriter<ConfigLoadError>,
+) where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+{
+    // The asset server sends a failed reload more than once
+    let mut reported = HashSet::new();
+    for failed in failed_events.read() {
+        if !load.0.iter().any(|handle| handle.id() == failed.id) || !reported.insert(failed.id) {
+            continue;
+        }
+
+        let file = failed.path.path().to_string_lossy().to_string();
+        let error = errors
+            .take(&file)
+            .unwrap_or_else(|| ConfigLoadError::new(&file, &failed.error));
+
+        error!("{}", error);
+        commands.insert_resource(error.clone());
+        error_events.write(error);
+    }
+}
+
+fn spawns_same<EXTENSION>(old: &PartPath<EXTENSION>, new: &PartPath<EXTENSION>) -> bool {
+    old.path == new.path
+        && old.label == new.label
+        && old.node == new.node
+        && old.transform == new.transform
+        && old.components == new.components
+}
+
+// Puts the part in the map and records how it differs from the entry it replaces
+fn replace_part<EXTENSION: Asset>(
+    parts_map: &mut PartsMap<EXTENSION>,
+    name: &str,
+    part: PartPath<EXTENSION>,
+    changed: &mut PartsMapChanged<EXTENSION>,
+    moved: &mut Vec<(String, String)>,
+) -> Option<PartPath<EXTENSION>> {
+    let old = parts_map.parts.insert(name.to_string(), part);
+    match &old {
+        Some(old) => {
+            let new = &parts_map[name];
+            if !spawns_same(old, new) {
+                changed.changed.push(name.to_string());
+                moved.push((name.to_string(), old.path.clone()));
+            } else if !same_data(old, new) {
+                changed.changed.push(name.to_string());
+            }
+        }
+        None => changed.added.push(name.to_string()),
+    }
+    old
+}
+
+// Data without a raw value, which is the case for RON configs, is treated as changed
+fn same_data<EXTENSION>(old: &PartPath<EXTENSION>, new: &PartPath<EXTENSION>) -> bool {
+    match (&old.data, &new.data) {
+        (None, None) => true,
+        (Some(_), Some(_)) => old.raw_data.is_some() && old.raw_data == new.raw_data,
+        _ => false,
+    }
+}
+
+// Rebuilds every spawned instance of the part in place so node and label parts are swapped too
+fn respawn_part<EXTENSION>(
+    name: &str,
+    old_path: &str,
+    parts_map: &PartsMap<EXTENSION>,
+    instances: &PartInstances,
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+) where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+{
+    // Parts of another `PartsMap` can share the name but not the file they were spawned from
+    let entities: Vec<_> = instances
+        .iter()
+        .filter(|(_, spawned)| spawned.name == name && spawned.path == old_path)
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in entities {
+        parts_map[name]
+            .load()
+            .replace(entity)
+            .build(commands, asset_server);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EmptyExtension;
+    use serde_json::{Value, json};
+
+    // The typed data is never compared, only the value it was read from
+    fn part(raw_data: Option<Value>) -> PartPath<EmptyExtension> {
+        let mut part: PartPath<EmptyExtension> =
+            serde_json::from_value(json!({ "path": "car.gltf" })).unwrap();
+        part.data = raw_data.is_some().then_some(EmptyExtension);
+        part.raw_data = raw_data;
+        part
+    }
+
+    #[test]
+    fn data_is_compared_as_written() {
+        let old = part(Some(json!({ "speed": 1.0 })));
+
+        assert!(same_data(&old, &part(Some(json!({ "speed": 1.0 })))));
+        assert!(!same_data(&old, &part(Some(json!({ "speed": 2.0 })))));
+        assert!(!same_data(&old, &part(None)));
+        assert!(same_data(&part(None), &part(None)));
+    }
+
+    #[test]
+    fn data_without_raw_value_is_changed() {
+        let mut old = part(None);
+        old.data = Some(EmptyExtension);
+
+        assert!(!same_data(&old, &old.clone()));
+    }
+}
@@ -1,13 +1,38 @@
+mod assembly;
+mod config_loader;
 mod config_singleton;
 mod extend_gltf_material;
+mod glob;
+mod hot_reload;
+mod part_components;
+mod part_id;
+mod part_label;
 mod part_loader;
 mod part_path;
+mod part_ready;
+mod part_transform;
 mod parts_map;
 mod plugin;
+mod preload;
+mod progress;
+mod socket;
+mod spawned_part;
+mod templates;
 
+pub use assembly::*;
+pub use config_loader::*;
 pub use config_singleton::*;
 pub use extend_gltf_material::*;
+pub use hot_reload::*;
+pub use part_components::*;
+pub use part_id::*;
 pub use part_path::*;
+pub use part_ready::*;
+pub use part_transform::*;
 pub use part_loader::*;
 pub use parts_map::*;
 pub use plugin::*;
+pub use preload::*;
+pub use progress::*;
+pub use socket::*;
+pub use spawned_part::*;
@@ -0,0 +1,85 @@
+use bevy::app::App;
+use bevy::ecs::reflect::{AppTypeRegistry, ReflectComponent};
+use bevy::prelude::{Entity, EntityWorldMut, Event, error};
+use bevy::reflect::TypeRegistry;
+use bevy::reflect::serde::TypedReflectDeserializer;
+use serde::de::DeserializeSeed;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+// Reports components from part configs that could not be inserted
+pub(crate) fn part_components(app: &mut App) {
+    app.add_event::<PartComponentError>();
+}
+
+/// Sent when a component from the `components` map of a part could not be inserted
+/// The remaining components of the part are still inserted
+#[derive(Event, Debug, Clone)]
+pub struct PartComponentError {
+    pub entity: Entity,
+    pub type_path: String,
+    pub message: String,
+}
+
+impl Display for PartComponentError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unable to insert component {} on {}: {}",
+            &self.type_path, self.entity, &self.message
+        )
+    }
+}
+
+impl Error for PartComponentError {}
+
+// Entity command inserting every component of the map through reflection
+pub(crate) fn insert_components(
+    components: HashMap<String, Value>,
+) -> impl FnOnce(EntityWorldMut) + Send + 'static {
+    move |mut entity: EntityWorldMut| {
+        let registry = entity.world_scope(|world| world.resource::<AppTypeRegistry>().clone());
+        let registry = registry.read();
+
+        let mut errors = vec![];
+        for (type_path, value) in components {
+            if let Err(message) = insert_component(&mut entity, &registry, &type_path, value) {
+                errors.push(PartComponentError {
+                    entity: entity.id(),
+                    type_path,
+                    message,
+                });
+            }
+        }
+
+        entity.world_scope(|world| {
+            for error in errors {
+                error!("{}", error);
+                world.send_event(error);
+            }
+        });
+    }
+}
+
+fn insert_component(
+    entity: &mut EntityWorldMut,
+    registry: &TypeRegistry,
+    type_path: &str,
+    value: Value,
+) -> Result<(), String> {
+    let registration = registry
+        .get_with_type_path(type_path)
+        .ok_or("type is not registered, add it with `App::register_type`")?;
+    let reflect_component = registration
+        .data::<ReflectComponent>()
+        .ok_or("type is not a component, add `#[reflect(Component)]` to it")?;
+
+    let component = TypedReflectDeserializer::new(registration, registry)
+        .deserialize(value)
+        .map_err(|error| error.to_string())?;
+
+    reflect_component.insert(entity, component.as_partial_reflect(), registry);
+    Ok(())
+}
@@ -0,0 +1,52 @@
+/// A known set of part names, usually generated with `part_ids!`
+/// Lets a `PartsMap` be indexed without raw strings and checked at load time with `require_part_ids`
+pub trait PartId: Copy + Send + Sync + 'static {
+    /// Every id in the set, used to check that the loaded config defines all of them
+    const ALL: &'static [Self];
+
+    /// The part name as written in the config
+    fn as_str(&self) -> &'static str;
+}
+
+/// Generates an enum implementing `PartId`
+/// Each variant maps to the part with the same name unless a different name is given after `=`
+///
+/// ```
+/// bevy_runtime_gltf_loader::part_ids! {
+///     pub enum Parts {
+///         DebugPart,
+///         Wheel = "wheel_small",
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! part_ids {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident $(= $part:literal)?),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        $vis enum $name {
+            $($variant),*
+        }
+
+        impl $crate::PartId for $name {
+            const ALL: &'static [Self] = &[$(Self::$variant),*];
+
+            fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $crate::part_ids!(@name $variant $($part)?)),*
+                }
+            }
+        }
+    };
+    (@name $variant:ident $part:literal) => {
+        $part
+    };
+    (@name $variant:ident) => {
+        stringify!($variant)
+    };
+}
@@ -0,0 +1,319 @@
+use bevy::app::{App, PostUpdate, Update};
+use bevy::asset::{AssetEvent, AssetId, AssetServer, Assets, Handle, LoadState};
+use bevy::gltf::{Gltf, GltfAssetLabel, GltfMaterialName, GltfMesh, GltfNode, GltfPrimitive};
+use bevy::prelude::{
+    ChildOf, Commands, Component, Entity, EventReader, Mesh3d, MeshMaterial3d, Name, Query, Res,
+    ResMut, Resource, Transform, Visibility, World, error,
+};
+use bevy::scene::{Scene, SceneRoot};
+use std::collections::HashMap;
+
+// Resolves labels that need the loaded `Gltf` asset
+pub(crate) fn part_labels(app: &mut App) {
+    app.init_resource::<LabelScenes>()
+        .add_systems(Update, resolve_labels)
+        .add_systems(PostUpdate, evict_label_scenes);
+}
+
+// Scenes built for a label or node of a `Gltf`, shared by every part spawning the same one
+// Handles are weak so the scenes are still dropped once no part uses them
+#[derive(Resource, Default)]
+struct LabelScenes(HashMap<(AssetId<Gltf>, String, bool), Handle<Scene>>);
+
+// A reloaded `Gltf` gets new scenes since the old ones point at its previous meshes
+fn evict_label_scenes(
+    mut scene_events: EventReader<AssetEvent<Scene>>,
+    mut gltf_events: EventReader<AssetEvent<Gltf>>,
+    mut cache: ResMut<LabelScenes>,
+) {
+    for event in scene_events.read() {
+        if let AssetEvent::Removed { id } = event {
+            cache.0.retain(|_, scene| scene.id() != *id);
+        }
+    }
+
+    for event in gltf_events.read() {
+        if let AssetEvent::Modified { id } | AssetEvent::Removed { id } = event {
+            cache.0.retain(|(gltf, _, _), _| gltf != id);
+        }
+    }
+}
+
+// Label of a part that is waiting for its GLTF file to load before anything is spawned
+#[derive(Component)]
+pub(crate) struct PendingPartLabel {
+    gltf: Handle<Gltf>,
+    label: String,
+    // Only look the label up as a node
+    node: bool,
+}
+
+impl PendingPartLabel {
+    pub(crate) fn new(gltf: Handle<Gltf>, label: &str) -> Self {
+        Self {
+            gltf,
+            label: label.to_string(),
+            node: false,
+        }
+    }
+
+    pub(crate) fn node(gltf: Handle<Gltf>, node: &str) -> Self {
+        Self {
+            gltf,
+            label: node.to_string(),
+            node: true,
+        }
+    }
+}
+
+// Labels like `Scene1` can be loaded straight away, every other label needs the `Gltf` asset
+pub(crate) fn scene_label(label: &str) -> Option<GltfAssetLabel> {
+    let index = label.strip_prefix("Scene")?.parse().ok()?;
+    Some(GltfAssetLabel::Scene(index))
+}
+
+// `Node3`
+fn node_label(label: &str) -> Option<usize> {
+    label.strip_prefix("Node")?.parse().ok()
+}
+
+// `Mesh0` or `Mesh0/Primitive1`
+fn mesh_label(label: &str) -> Option<(usize, Option<usize>)> {
+    let (mesh, primitive) = match label.split_once('/') {
+        Some((mesh, primitive)) => (mesh, Some(primitive)),
+        None => (label, None),
+    };
+
+    let mesh = mesh.strip_prefix("Mesh")?.parse().ok()?;
+    let primitive = match primitive {
+        Some(primitive) => Some(primitive.strip_prefix("Primitive")?.parse().ok()?),
+        None => None,
+    };
+    Some((mesh, primitive))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn resolve_labels(
+    mut commands: Commands,
+    pending: Query<(Entity, &PendingPartLabel)>,
+    asset_server: Res<AssetServer>,
+    gltfs: Res<Assets<Gltf>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    gltf_nodes: Res<Assets<GltfNode>>,
+    mut scenes: ResMut<Assets<Scene>>,
+    mut cache: ResMut<LabelScenes>,
+) {
+    for (entity, PendingPartLabel { gltf, label, node }) in pending.iter() {
+        let Some(gltf_asset) = gltfs.get(gltf) else {
+            if let LoadState::Failed(load_error) = asset_server.load_state(gltf) {
+                error!("unable to resolve label {}: {}", label, load_error);
+                commands.entity(entity).remove::<PendingPartLabel>();
+            }
+            continue;
+        };
+
+        commands.entity(entity).remove::<PendingPartLabel>();
+
+        let key = (gltf.id(), label.clone(), *node);
+        let cached = cache
+            .0
+            .get(&key)
+            .and_then(|scene| scenes.get_strong_handle(scene.id()));
+        let scene = cached.or_else(|| {
+            label_scene(
+                gltf_asset,
+                label,
+                *node,
+                &gltf_nodes,
+                &gltf_meshes,
+                &mut scenes,
+            )
+        });
+        if let Some(scene) = &scene {
+            cache.0.insert(key, scene.clone_weak());
+        }
+
+        match scene {
+            Some(scene) => {
+                commands.entity(entity).insert(SceneRoot(scene));
+            }
+            None => error!(
+                "{} is not a {} of {}",
+                label,
+                if *node { "node" } else { "scene, mesh or node" },
+                asset_server
+                    .get_path(gltf)
+                    .map(|path| path.to_string())
+                    .unwrap_or_default()
+            ),
+        }
+    }
+}
+
+// Scene spawned for `label`, only generated when the label is not already a scene of the `Gltf`
+fn label_scene(
+    gltf_asset: &Gltf,
+    label: &str,
+    node: bool,
+    gltf_nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+    scenes: &mut Assets<Scene>,
+) -> Option<Handle<Scene>> {
+    let node_handle = gltf_asset
+        .named_nodes
+        .get(label)
+        .or_else(|| node_label(label).and_then(|index| gltf_asset.nodes.get(index)));
+
+    if node {
+        node_handle
+            .and_then(|node| node_scene(gltf_asset, node, gltf_nodes, gltf_meshes))
+            .map(|scene| scenes.add(scene))
+    } else if let Some(scene) = gltf_asset.named_scenes.get(label) {
+        Some(scene.clone())
+    } else if let Some((mesh, primitive)) = mesh_label(label) {
+        gltf_asset
+            .meshes
+            .get(mesh)
+            .and_then(|mesh| gltf_meshes.get(mesh))
+            .and_then(|mesh| mesh_scene(gltf_asset, mesh, primitive))
+            .map(|scene| scenes.add(scene))
+    } else if let Some(mesh) = gltf_asset.named_meshes.get(label) {
+        gltf_meshes
+            .get(mesh)
+            .and_then(|mesh| mesh_scene(gltf_asset, mesh, None))
+            .map(|scene| scenes.add(scene))
+    } else if let Some(node) = node_handle {
+        node_scene(gltf_asset, node, gltf_nodes, gltf_meshes).map(|scene| scenes.add(scene))
+    } else {
+        None
+    }
+}
+
+// Scene holding one entity per primitive so meshes spawn the same way as regular GLTF scenes
+fn mesh_scene(gltf: &Gltf, mesh: &GltfMesh, primitive: Option<usize>) -> Option<Scene> {
+    let primitives = match primitive {
+        Some(primitive) => vec![mesh.primitives.get(primitive)?],
+        None => mesh.primitives.iter().collect(),
+    };
+
+    let mut world = World::new();
+    let root = world
+        .spawn((
+            Name::new(mesh.name.clone()),
+            Transform::default(),
+            Visibility::default(),
+        ))
+        .id();
+    spawn_primitives(&mut world, gltf, root, primitives);
+
+    Some(Scene::new(world))
+}
+
+// Scene holding the node and all of its children, the node itself is placed at the part origin
+fn node_scene(
+    gltf: &Gltf,
+    node: &Handle<GltfNode>,
+    gltf_nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+) -> Option<Scene> {
+    let mut world = World::new();
+    let root = spawn_node(
+        &mut world,
+        gltf,
+        gltf_nodes.get(node)?,
+        gltf_nodes,
+        gltf_meshes,
+    );
+    world.entity_mut(root).insert(Transform::default());
+
+    Some(Scene::new(world))
+}
+
+fn spawn_node(
+    world: &mut World,
+    gltf: &Gltf,
+    node: &GltfNode,
+    gltf_nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+) -> Entity {
+    let entity = world
+        .spawn((
+            Name::new(node.name.clone()),
+            node.transform,
+            Visibility::default(),
+        ))
+        .id();
+
+    if let Some(mesh) = node.mesh.as_ref().and_then(|mesh| gltf_meshes.get(mesh)) {
+        spawn_primitives(world, gltf, entity, mesh.primitives.iter().collect());
+    }
+
+    for child in node
+        .children
+        .iter()
+        .filter_map(|child| gltf_nodes.get(child))
+    {
+        let child = spawn_node(world, gltf, child, gltf_nodes, gltf_meshes);
+        world.entity_mut(child).insert(ChildOf(entity));
+    }
+
+    entity
+}
+
+fn spawn_primitives(
+    world: &mut World,
+    gltf: &Gltf,
+    parent: Entity,
+    primitives: Vec<&GltfPrimitive>,
+) {
+    for primitive in primitives {
+        let mut entity = world.spawn((
+            Name::new(primitive.name.clone()),
+            Mesh3d(primitive.mesh.clone()),
+            MeshMaterial3d(primitive.material.clone().unwrap_or_default()),
+            Transform::default(),
+            ChildOf(parent),
+        ));
+
+        // Same as the GLTF loader so material extension filters work on these meshes too
+        let material_name = primitive.material.as_ref().and_then(|material| {
+            gltf.named_materials
+                .iter()
+                .find(|(_, named)| *named == material)
+        });
+        if let Some((name, _)) = material_name {
+            entity.insert(GltfMaterialName(name.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scene_labels() {
+        assert_eq!(scene_label("Scene0"), Some(GltfAssetLabel::Scene(0)));
+        assert_eq!(scene_label("Scene12"), Some(GltfAssetLabel::Scene(12)));
+        assert_eq!(scene_label("Scene"), None);
+        assert_eq!(scene_label("SceneA"), None);
+        assert_eq!(scene_label("Mesh0"), None);
+    }
+
+    #[test]
+    fn node_labels() {
+        assert_eq!(node_label("Node3"), Some(3));
+        assert_eq!(node_label("Node"), None);
+        assert_eq!(node_label("Wheel"), None);
+    }
+
+    #[test]
+    fn mesh_labels() {
+        assert_eq!(mesh_label("Mesh0"), Some((0, None)));
+        assert_eq!(mesh_label("Mesh2/Primitive1"), Some((2, Some(1))));
+        assert_eq!(mesh_label("Mesh2/Material1"), None);
+        assert_eq!(mesh_label("Mesh2/Primitive"), None);
+        assert_eq!(mesh_label("Meshes"), None);
+        assert_eq!(mesh_label("Scene0"), None);
+    }
+}
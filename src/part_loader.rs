@@ -1,17 +1,50 @@
-use crate::{EmptyMaterialExtension, WithMaterialExtension};
+use crate::extend_gltf_material::MaterialFilter;
+use crate::part_components::insert_components;
+use crate::part_label::{PendingPartLabel, scene_label};
+use crate::part_ready::{
+    InsertOnce, PendingPartReady, ReadyBundles, ReadyCallback, ready_callback,
+};
+use crate::socket::{PendingSocket, detach_from_sockets};
+use crate::spawned_part::SpawnedPart;
+use crate::{EmptyMaterialExtension, MaterialExtensionFilter, WithMaterialExtension};
 use bevy::asset::AssetServer;
 use bevy::pbr::MaterialExtension;
-use bevy::prelude::{BuildChildrenTransformExt, Commands, Entity, GltfAssetLabel, Res, SceneRoot, Transform};
+use bevy::prelude::{BuildChildrenTransformExt, Bundle, Commands, Component, EntityWorldMut, In, IntoSystem, Entity, EntityRef, GltfAssetLabel, Res, SceneRoot, Transform};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 pub struct PartLoader<'a, EXTENSION, MATERIAL = EmptyMaterialExtension> {
+    // Key of the part in the `PartsMap`, empty when created with `new`
+    name: &'a str,
     // Spawns a child node on this entity
     on: Option<Entity>,
+    // Reuses this part entity instead of spawning a new one
+    replace: Option<Entity>,
+    // Spawns a child node on the named descendant of this entity once its scene is ready
+    socket: Option<(Entity, &'a str)>,
     // Sets an offset for the model
     offset: Option<Transform>,
+    // Transform from the part config, the offset is applied on top of it
+    base_transform: Option<Transform>,
     path: &'a String,
     extend_material: Option<MATERIAL>,
+    // Meshes that get `extend_material`, every mesh of the part when empty
+    material_filters: Vec<MaterialFilter>,
     asset_label: Option<GltfAssetLabel>,
+    // Label from the part config, `asset_label` takes priority over it
+    label: Option<&'a String>,
+    // Spawns only this GLTF node and its children, `asset_label` takes priority over it
+    node: Option<&'a str>,
+    // Reflected components from the part config
+    components: Option<&'a HashMap<String, Value>>,
+    // Extra components inserted when the part is built
+    bundles: Vec<InsertOnce>,
+    // Extra components inserted once the part scene is ready
+    ready_bundles: ReadyBundles,
+    // Systems run once with the part entity after `PartReady`
+    on_ready: Vec<ReadyCallback>,
     phantom: PhantomData<EXTENSION>,
 }
 
@@ -21,12 +54,23 @@ where
 {
     pub fn new(path: &'a String) -> Self {
         PartLoader {
+            name: "",
             on: None,
+            replace: None,
+            socket: None,
             offset: None,
+            base_transform: None,
             asset_label: None,
+            label: None,
+            node: None,
+            components: None,
+            bundles: vec![],
+            ready_bundles: ReadyBundles::default(),
+            on_ready: vec![],
             path,
             phantom: Default::default(),
             extend_material: None,
+            material_filters: vec![],
         }
     }
 
@@ -35,13 +79,40 @@ where
         self
     }
 
+    /// Turn `entity` into this part instead of spawning a new entity
+    /// The old scene is despawned while the parent, `Transform` and other components are kept
+    /// Parts attached to a socket of the old scene are attached to the same socket of the new one
+    /// Material extensions of the old part are applied again, `extend_material` swaps the one of the same type
+    pub fn replace(mut self, entity: Entity) -> Self {
+        self.replace = Some(entity);
+        self
+    }
+
+    /// Parent the part to the descendant of `entity` named `socket`
+    /// Waits for the scene of `entity` to be ready and sends a `SocketNotFoundError` if there is no such descendant
+    pub fn on_socket(mut self, entity: Entity, socket: &'a str) -> Self {
+        self.socket = Some((entity, socket));
+        self
+    }
+
     pub fn material_trait<NewMaterial>(self) -> PartLoader<'a, EXTENSION, NewMaterial> {
         PartLoader {
+            name: self.name,
             on: self.on,
+            replace: self.replace,
+            socket: self.socket,
             offset: self.offset,
+            base_transform: self.base_transform,
             path: self.path,
             extend_material: None,
+            material_filters: self.material_filters,
             asset_label: self.asset_label,
+            label: self.label,
+            node: self.node,
+            components: self.components,
+            bundles: self.bundles,
+            ready_bundles: self.ready_bundles,
+            on_ready: self.on_ready,
             phantom: Default::default(),
         }
     }
@@ -51,6 +122,44 @@ where
         self
     }
 
+    pub(crate) fn base_transform(mut self, transform: Transform) -> Self {
+        self.base_transform = Some(transform);
+        self
+    }
+
+    /// Insert `bundle` on the part entity when it is built, can be called more than once
+    pub fn with<B: Bundle>(mut self, bundle: B) -> Self {
+        self.bundles.push(Box::new(move |entity_commands| {
+            entity_commands.insert(bundle);
+        }));
+        self
+    }
+
+    /// Insert `bundle` on the part entity once its scene is spawned
+    pub fn with_on_ready<B: Bundle>(mut self, bundle: B) -> Self {
+        self.ready_bundles.root.push(Box::new(move |entity_commands| {
+            entity_commands.insert(bundle);
+        }));
+        self
+    }
+
+    /// Insert a copy of `bundle` on every mesh of the part once its scene is spawned
+    pub fn with_on_ready_meshes<B: Bundle + Clone>(mut self, bundle: B) -> Self {
+        self.ready_bundles.meshes.push(Box::new(move |entity_commands| {
+            entity_commands.insert(bundle.clone());
+        }));
+        self
+    }
+
+    /// Run `system` once with the part entity as input when `PartReady` is sent for it
+    pub fn on_ready<M>(
+        mut self,
+        system: impl IntoSystem<In<Entity>, (), M> + Send + Sync + 'static,
+    ) -> Self {
+        self.on_ready.push(ready_callback(system));
+        self
+    }
+
     pub fn extend_material<NewMaterial>(
         self,
         material: NewMaterial,
@@ -60,43 +169,324 @@ where
         new
     }
 
+    /// Only give `extend_material` to meshes whose node, or a parent node in the part, has a `Name` matching `pattern`
+    /// `*` matches any run of characters and `?` a single one, a mesh gets the extension when it matches any filter
+    pub fn extend_material_on_nodes(mut self, pattern: &str) -> Self {
+        self.material_filters
+            .push(MaterialFilter::Node(pattern.to_string()));
+        self
+    }
+
+    /// Only give `extend_material` to meshes whose GLTF material name matches `pattern`
+    pub fn extend_material_on_materials(mut self, pattern: &str) -> Self {
+        self.material_filters
+            .push(MaterialFilter::Material(pattern.to_string()));
+        self
+    }
+
+    /// Only give `extend_material` to meshes `predicate` returns true for, it runs once the part scene is spawned
+    pub fn extend_material_where(
+        mut self,
+        predicate: impl Fn(EntityRef) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.material_filters
+            .push(MaterialFilter::Predicate(Arc::new(predicate)));
+        self
+    }
+
     pub fn asset_label(mut self, asset_label: GltfAssetLabel) -> Self {
         self.asset_label = Some(asset_label);
         self
     }
 
+    pub(crate) fn config_label(mut self, label: &'a String) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Spawn a single node of the GLTF file by name, along with its meshes and children
+    /// Nothing is spawned until the GLTF file is loaded
+    pub fn node(mut self, node: &'a str) -> Self {
+        self.node = Some(node);
+        self
+    }
+
+    pub(crate) fn config_name(mut self, name: &'a str) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub(crate) fn config_components(mut self, components: &'a HashMap<String, Value>) -> Self {
+        self.components = Some(components);
+        self
+    }
+
     pub fn build(self, commands: &mut Commands, asset_server: &Res<AssetServer>) -> Entity {
         let Self {
+            name,
             on,
+            replace,
+            socket,
             offset,
+            base_transform,
             path,
             asset_label,
+            label,
+            node,
             extend_material,
+            material_filters,
+            components,
+            bundles,
+            ready_bundles,
+            on_ready,
             ..
         } = self;
 
-        let mut entity_commands = commands.spawn_empty();
+        let spawned_part = SpawnedPart {
+            name: name.to_string(),
+            path: path.clone(),
+            label: asset_label
+                .as_ref()
+                .map(|asset_label| asset_label.to_string())
+                .or(label.cloned()),
+            node: node.map(str::to_string),
+        };
+
+        let mut entity_commands = match replace {
+            Some(entity) => {
+                let mut entity_commands = commands.entity(entity);
+                // Work still queued for the old part
+                entity_commands
+                    .remove::<(
+                        PendingPartLabel,
+                        PendingSocket,
+                        ReadyBundles,
+                        PendingPartReady,
+                    )>()
+                    .insert(spawned_part)
+                    .queue(detach_from_sockets);
+                entity_commands
+            }
+            None => commands.spawn(spawned_part),
+        };
 
         if let Some(on) = on {
             entity_commands.set_parent_in_place(on);
         }
 
-        let path = asset_label
-            .unwrap_or(GltfAssetLabel::Scene(0))
-            .from_asset(path.clone());
+        if let Some((entity, socket)) = socket {
+            entity_commands.insert(PendingSocket::new(entity, socket));
+        }
+
+        // Names and mesh labels are only known once the GLTF file is loaded
+        let asset_label = asset_label.or_else(|| match (node, label) {
+            (Some(_), _) => None,
+            (None, Some(label)) => scene_label(label),
+            (None, None) => Some(GltfAssetLabel::Scene(0)),
+        });
 
-        let scene = asset_server.load(path);
+        let transform = match (offset, base_transform) {
+            (Some(offset), Some(base)) => Some(offset.mul_transform(base)),
+            (offset, base) => offset.or(base),
+        };
 
-        if let Some(offset) = offset {
-            entity_commands.insert(offset);
+        match (replace, offset) {
+            // Keep where the old part was placed but swap its base transform for the new one
+            (Some(_), None) => {
+                entity_commands.queue(rebase(base_transform.unwrap_or_default()));
+            }
+            _ => {
+                if let Some(transform) = transform {
+                    entity_commands.insert(transform);
+                }
+            }
         }
+        entity_commands.insert(PartBaseTransform(base_transform.unwrap_or_default()));
 
-        entity_commands.insert(SceneRoot(scene));
+        if let Some(asset_label) = asset_label {
+            let scene = asset_server.load(asset_label.from_asset(path.clone()));
+            entity_commands.insert(SceneRoot(scene));
+        } else if let Some(node) = node {
+            let gltf = asset_server.load(path.clone());
+            entity_commands.insert(PendingPartLabel::node(gltf, node));
+        } else if let Some(label) = label {
+            let gltf = asset_server.load(path.clone());
+            entity_commands.insert(PendingPartLabel::new(gltf, label));
+        }
 
         if let Some(material) = extend_material {
             entity_commands.insert(WithMaterialExtension(material));
+            // A replaced part keeps the filters of the old part unless new ones are given
+            if material_filters.is_empty() {
+                entity_commands.remove::<MaterialExtensionFilter<MATERIAL>>();
+            } else {
+                entity_commands.insert(MaterialExtensionFilter::<MATERIAL>::new(material_filters));
+            }
+        }
+
+        if let Some(components) = components {
+            entity_commands.queue(insert_components(components.clone()));
+        }
+
+        for insert in bundles {
+            insert(&mut entity_commands);
         }
 
+        if !ready_bundles.is_empty() {
+            entity_commands.insert(ready_bundles);
+        }
+
+        entity_commands.insert(PendingPartReady::new(name, on_ready));
+
         entity_commands.id()
     }
 }
+
+// Base transform the part was built with, used to swap it out on `PartLoader::replace`
+#[derive(Component)]
+pub(crate) struct PartBaseTransform(Transform);
+
+fn rebase(new_base: Transform) -> impl FnOnce(EntityWorldMut) + Send + 'static {
+    move |mut entity: EntityWorldMut| {
+        let old_base = entity
+            .get::<PartBaseTransform>()
+            .map(|base| base.0)
+            .unwrap_or_default();
+
+        if let Some(mut transform) = entity.get_mut::<Transform>() {
+            let placement = transform.compute_matrix() * old_base.compute_matrix().inverse();
+            *transform = Transform::from_matrix(placement * new_base.compute_matrix());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EmptyExtension, PartPath};
+    use bevy::MinimalPlugins;
+    use bevy::asset::AssetPlugin;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{App, Quat, Vec3};
+    use bevy::scene::ScenePlugin;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), ScenePlugin));
+        app
+    }
+
+    fn build(
+        app: &mut App,
+        replace: Option<Entity>,
+        offset: Option<Transform>,
+        base: Transform,
+    ) -> Entity {
+        app.world_mut()
+            .run_system_once(
+                move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                    let path = "part.gltf".to_string();
+                    let mut loader = PartLoader::<EmptyExtension>::new(&path).base_transform(base);
+                    if let Some(entity) = replace {
+                        loader = loader.replace(entity);
+                    }
+                    if let Some(offset) = offset {
+                        loader = loader.offset(offset);
+                    }
+                    loader.build(&mut commands, &asset_server)
+                },
+            )
+            .unwrap()
+    }
+
+    fn assert_transform(actual: &Transform, expected: Transform) {
+        assert!(
+            actual.translation.abs_diff_eq(expected.translation, 1e-4),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        assert!(
+            actual.rotation.abs_diff_eq(expected.rotation, 1e-4),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        assert!(
+            actual.scale.abs_diff_eq(expected.scale, 1e-4),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn replace_swaps_the_base_transform_under_the_offset() {
+        let mut app = app();
+        let offset = Transform::from_xyz(5.0, 0.0, 0.0)
+            .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        let old_base = Transform::from_xyz(0.0, 1.0, 0.0).with_scale(Vec3::splat(2.0));
+        let new_base = Transform::from_xyz(0.0, 0.0, 3.0);
+
+        let part = build(&mut app, None, Some(offset), old_base);
+        build(&mut app, Some(part), None, new_base);
+
+        assert_transform(
+            app.world().get::<Transform>(part).unwrap(),
+            offset.mul_transform(new_base),
+        );
+    }
+
+    #[test]
+    fn replace_keeps_where_the_part_was_moved() {
+        let mut app = app();
+        let old_base = Transform::from_xyz(0.0, 1.0, 0.0);
+        let new_base = Transform::from_rotation(Quat::from_rotation_x(1.0));
+
+        let part = build(&mut app, None, None, old_base);
+        let moved = Transform::from_xyz(-2.0, 0.0, 4.0);
+        *app.world_mut().get_mut::<Transform>(part).unwrap() = moved.mul_transform(old_base);
+        build(&mut app, Some(part), None, new_base);
+
+        assert_transform(
+            app.world().get::<Transform>(part).unwrap(),
+            moved.mul_transform(new_base),
+        );
+    }
+
+    #[test]
+    fn build_applies_the_offset_on_top_of_the_config_transform() {
+        let mut app = app();
+        let part: PartPath<EmptyExtension> = serde_json::from_value(serde_json::json!({
+            "path": "part.gltf",
+            "transform": { "translation": [0.0, 1.0, 0.0], "scale": 2.0 },
+        }))
+        .unwrap();
+        let offset = Transform::from_xyz(3.0, 0.0, 0.0)
+            .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+
+        let entity = app
+            .world_mut()
+            .run_system_once(
+                move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                    part.load()
+                        .offset(offset)
+                        .build(&mut commands, &asset_server)
+                },
+            )
+            .unwrap();
+
+        // The config transform is applied in the part space, then the offset places the part
+        let transform = app.world().get::<Transform>(entity).unwrap();
+        assert_transform(
+            transform,
+            Transform::from_xyz(2.0, 0.0, 0.0)
+                .with_rotation(offset.rotation)
+                .with_scale(Vec3::splat(2.0)),
+        );
+        assert_transform(
+            transform,
+            offset.mul_transform(Transform::from_xyz(0.0, 1.0, 0.0).with_scale(Vec3::splat(2.0))),
+        );
+    }
+}
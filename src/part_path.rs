@@ -2,17 +2,52 @@ use std::error::Error;
 use std::fmt::Display;
 use bevy::prelude::BevyError;
 use crate::part_loader::PartLoader;
+use crate::part_transform::PartTransform;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PartPath<EXTENSION> {
+    // Key of the part in the `PartsMap`, filled in by the config loader
+    #[serde(skip)]
+    pub name: String,
     pub path: String,
     pub data: Option<EXTENSION>,
+    // Applied to the spawned model before any `PartLoader::offset`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<PartTransform>,
+    // GLTF label to spawn instead of the first scene, `Scene1`, `Mesh0/Primitive0` or a scene, mesh or node name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    // Name of a single GLTF node to spawn together with its children instead of a whole scene
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    // Components inserted on the part entity, keyed by their reflect type path, not supported in RON configs
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub components: HashMap<String, Value>,
+    // `data` as it was written in the config, compared on reload since `EXTENSION` has no `PartialEq`
+    // Left empty for RON, whose untyped values drop enum variant names
+    #[serde(skip)]
+    pub(crate) raw_data: Option<Value>,
 }
 
 impl<EXTENSION> PartPath<EXTENSION> {
-    pub fn load(&self) -> PartLoader<EXTENSION> {
-        PartLoader::new(&self.path)
+    pub fn load(&self) -> PartLoader<'_, EXTENSION> {
+        let mut loader = PartLoader::new(&self.path).config_name(&self.name);
+        if let Some(label) = &self.label {
+            loader = loader.config_label(label);
+        }
+        if let Some(node) = &self.node {
+            loader = loader.node(node);
+        }
+        if !self.components.is_empty() {
+            loader = loader.config_components(&self.components);
+        }
+        match self.transform {
+            Some(transform) => loader.base_transform(transform.into()),
+            None => loader,
+        }
     }
 
     pub fn data(&self) -> Result<&EXTENSION, BevyError> {
@@ -0,0 +1,160 @@
+use bevy::app::{App, PostUpdate};
+use bevy::ecs::system::{EntityCommands, RunSystemOnce};
+use bevy::prelude::{
+    Children, Commands, Component, Entity, Event, EventWriter, In, IntoSystem, Mesh3d, Query,
+    Trigger, With, World, error,
+};
+use bevy::scene::SceneInstanceReady;
+
+// Runs the work that has to wait for a spawned part scene
+pub(crate) fn part_ready(app: &mut App) {
+    app.add_event::<PartReady>()
+        .add_observer(insert_ready_bundles)
+        .add_observer(mark_ready)
+        .add_systems(PostUpdate, send_part_ready);
+}
+
+/// Triggered on a spawned part and sent as an event once its scene is spawned
+/// Any material extension and `with_on_ready` bundles are already applied by then,
+/// a part whose base materials are still loading waits for them to load or fail
+#[derive(Event, Debug, Clone)]
+pub struct PartReady {
+    pub entity: Entity,
+    // Empty when the part was not loaded through a `PartsMap`
+    pub part_name: String,
+}
+
+pub(crate) type ReadyCallback = Box<dyn FnOnce(&mut World, Entity) + Send + Sync>;
+
+pub(crate) fn ready_callback<M>(
+    system: impl IntoSystem<In<Entity>, (), M> + Send + Sync + 'static,
+) -> ReadyCallback {
+    Box::new(move |world, entity| {
+        if let Err(run_error) = world.run_system_once_with(system, entity) {
+            error!(
+                "unable to run on_ready callback for {}: {}",
+                entity, run_error
+            );
+        }
+    })
+}
+
+// Part whose `PartReady` has not been sent yet
+#[derive(Component)]
+pub(crate) struct PendingPartReady {
+    part_name: String,
+    callbacks: Vec<ReadyCallback>,
+    // Set when the scene is spawned, the event waits for the commands of the other observers
+    ready: bool,
+}
+
+impl PendingPartReady {
+    pub(crate) fn new(part_name: &str, callbacks: Vec<ReadyCallback>) -> Self {
+        Self {
+            part_name: part_name.to_string(),
+            callbacks,
+            ready: false,
+        }
+    }
+}
+
+// Number of material extensions a mesh is still waiting on, the part holds its `PartReady` until none are left
+#[derive(Component)]
+pub(crate) struct MaterialPending(pub(crate) usize);
+
+pub(crate) type InsertOnce = Box<dyn FnOnce(&mut EntityCommands) + Send + Sync>;
+pub(crate) type InsertEach = Box<dyn Fn(&mut EntityCommands) + Send + Sync>;
+
+// Bundles given to `PartLoader::with_on_ready` and `PartLoader::with_on_ready_meshes`
+#[derive(Component, Default)]
+pub(crate) struct ReadyBundles {
+    pub(crate) root: Vec<InsertOnce>,
+    pub(crate) meshes: Vec<InsertEach>,
+}
+
+impl ReadyBundles {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.root.is_empty() && self.meshes.is_empty()
+    }
+}
+
+fn insert_ready_bundles(
+    trigger: Trigger<SceneInstanceReady>,
+    mut commands: Commands,
+    mut bundles: Query<&mut ReadyBundles>,
+    children: Query<&Children>,
+    meshes: Query<(), With<Mesh3d>>,
+) {
+    let target = trigger.target();
+
+    let Ok(mut bundles) = bundles.get_mut(target) else {
+        return;
+    };
+    let ReadyBundles {
+        root,
+        meshes: mesh_inserts,
+    } = std::mem::take(&mut *bundles);
+
+    let mut entity_commands = commands.entity(target);
+    entity_commands.remove::<ReadyBundles>();
+    for insert in root {
+        insert(&mut entity_commands);
+    }
+
+    if mesh_inserts.is_empty() {
+        return;
+    }
+
+    for child in children.iter_descendants(target) {
+        if meshes.get(child).is_err() {
+            continue;
+        }
+
+        let mut entity_commands = commands.entity(child);
+        for insert in mesh_inserts.iter() {
+            insert(&mut entity_commands);
+        }
+    }
+}
+
+fn mark_ready(trigger: Trigger<SceneInstanceReady>, mut pending: Query<&mut PendingPartReady>) {
+    if let Ok(mut pending) = pending.get_mut(trigger.target()) {
+        pending.ready = true;
+    }
+}
+
+// Scenes are spawned after `Update` so this sees the part once every observer is done with it
+fn send_part_ready(
+    mut commands: Commands,
+    mut pending: Query<(Entity, &mut PendingPartReady)>,
+    children: Query<&Children>,
+    materials: Query<(), With<MaterialPending>>,
+    mut events: EventWriter<PartReady>,
+) {
+    for (entity, mut pending) in pending.iter_mut() {
+        if !pending.ready {
+            continue;
+        }
+
+        let waiting = std::iter::once(entity)
+            .chain(children.iter_descendants(entity))
+            .any(|child| materials.contains(child));
+        if waiting {
+            continue;
+        }
+
+        let event = PartReady {
+            entity,
+            part_name: std::mem::take(&mut pending.part_name),
+        };
+        let callbacks = std::mem::take(&mut pending.callbacks);
+
+        commands.entity(entity).remove::<PendingPartReady>();
+        commands.trigger_targets(event.clone(), entity);
+        events.write(event);
+
+        for callback in callbacks {
+            commands.queue(move |world: &mut World| callback(world, entity));
+        }
+    }
+}
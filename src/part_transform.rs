@@ -0,0 +1,129 @@
+use bevy::math::{EulerRot, Quat, Vec3};
+use bevy::prelude::Transform;
+use serde::{Deserialize, Serialize};
+
+/// Base transform of a part, used to fix models exported at the wrong scale or orientation
+/// Every field is optional and falls back to the identity transform
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartTransform {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<[f32; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<PartRotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<PartScale>,
+}
+
+/// Written as `{ "euler": [x, y, z] }` in degrees applied in XYZ order, or as `{ "quat": [x, y, z, w] }`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PartRotation {
+    Euler([f32; 3]),
+    Quat([f32; 4]),
+}
+
+/// Either one factor for every axis or one factor per axis
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PartScale {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+impl From<PartRotation> for Quat {
+    fn from(rotation: PartRotation) -> Self {
+        match rotation {
+            PartRotation::Euler([x, y, z]) => Quat::from_euler(
+                EulerRot::XYZ,
+                x.to_radians(),
+                y.to_radians(),
+                z.to_radians(),
+            ),
+            PartRotation::Quat(quat) => Quat::from_array(quat).normalize(),
+        }
+    }
+}
+
+impl From<PartScale> for Vec3 {
+    fn from(scale: PartScale) -> Self {
+        match scale {
+            PartScale::Uniform(scale) => Vec3::splat(scale),
+            PartScale::Axes(scale) => Vec3::from_array(scale),
+        }
+    }
+}
+
+impl From<PartTransform> for Transform {
+    fn from(transform: PartTransform) -> Self {
+        Transform {
+            translation: transform
+                .translation
+                .map(Vec3::from_array)
+                .unwrap_or(Vec3::ZERO),
+            rotation: transform.rotation.map(Quat::from).unwrap_or(Quat::IDENTITY),
+            scale: transform.scale.map(Vec3::from).unwrap_or(Vec3::ONE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transform(config: serde_json::Value) -> Transform {
+        serde_json::from_value::<PartTransform>(config)
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn empty_transform_is_identity() {
+        assert_eq!(transform(json!({})), Transform::IDENTITY);
+    }
+
+    #[test]
+    fn euler_is_in_degrees_applied_in_xyz_order() {
+        let rotation = transform(json!({ "rotation": { "euler": [90.0, 0.0, 90.0] } })).rotation;
+
+        // X first, then Z in the rotated frame
+        let expected = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)
+            * Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        assert!(rotation.abs_diff_eq(expected, 1e-5), "{:?}", rotation);
+        assert!(
+            (rotation * Vec3::X).abs_diff_eq(Vec3::Z, 1e-5),
+            "{:?}",
+            rotation * Vec3::X
+        );
+    }
+
+    #[test]
+    fn quat_is_normalized() {
+        let rotation = transform(json!({ "rotation": { "quat": [0.0, 2.0, 0.0, 2.0] } })).rotation;
+
+        assert!(rotation.is_normalized());
+        assert!(rotation.abs_diff_eq(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2), 1e-5));
+    }
+
+    #[test]
+    fn scale_is_uniform_or_per_axis() {
+        assert_eq!(
+            serde_json::from_value::<PartScale>(json!(2.0)).unwrap(),
+            PartScale::Uniform(2.0)
+        );
+        assert_eq!(transform(json!({ "scale": 0.5 })).scale, Vec3::splat(0.5));
+        assert_eq!(
+            transform(json!({ "scale": [1.0, 2.0, 3.0] })).scale,
+            Vec3::new(1.0, 2.0, 3.0)
+        );
+        assert!(serde_json::from_value::<PartScale>(json!([1.0, 2.0])).is_err());
+    }
+
+    #[test]
+    fn translation_is_kept() {
+        assert_eq!(
+            transform(json!({ "translation": [1.0, -2.0, 3.0] })).translation,
+            Vec3::new(1.0, -2.0, 3.0)
+        );
+    }
+}
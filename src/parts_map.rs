@@ -1,17 +1,35 @@
-use crate::PartPath;
+use crate::assembly::{Assembly, AssemblyLoader, AssemblyNotFoundError};
+use crate::{PartId, PartPath};
 use bevy::prelude::{Asset, BevyError, Deref, Reflect, Resource};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Display;
+use std::ops::Index;
 
 // Json loaded map of all available parts
 #[derive(Resource, Deref, Asset, Reflect, Deserialize)]
-pub struct PartsMap<EXTENSION: Asset>(pub(crate) HashMap<String, PartPath<EXTENSION>>);
+#[serde(transparent)]
+pub struct PartsMap<EXTENSION: Asset> {
+    #[deref]
+    pub(crate) parts: HashMap<String, PartPath<EXTENSION>>,
+    // Config file each part was read from, only tracked by the config loader
+    #[serde(skip)]
+    #[reflect(ignore)]
+    pub(crate) sources: HashMap<String, String>,
+    // Assemblies read from the `$assemblies` section of the configs
+    #[serde(skip)]
+    #[reflect(ignore)]
+    pub(crate) assemblies: HashMap<String, Assembly>,
+}
 
 impl<EXTENSION: Asset> Default for PartsMap<EXTENSION> {
     fn default() -> Self {
-        Self(HashMap::new())
+        Self {
+            parts: HashMap::new(),
+            sources: HashMap::new(),
+            assemblies: HashMap::new(),
+        }
     }
 }
 
@@ -19,6 +37,66 @@ impl<EXTENSION: Asset> PartsMap<EXTENSION> {
     pub fn load_part(&self, name: &str) -> Result<&PartPath<EXTENSION>, BevyError> {
         self.get(name).ok_or(BevyError::from(PartNotFoundError::new(name)))
     }
+
+    pub fn assemblies(&self) -> &HashMap<String, Assembly> {
+        &self.assemblies
+    }
+
+    /// Builder spawning the assembly called `name`, fails if the assembly or any of its parts are missing
+    pub fn assemble(&self, name: &str) -> Result<AssemblyLoader<'_, EXTENSION>, BevyError> {
+        let assembly = self.assemblies.get(name).ok_or(AssemblyNotFoundError {
+            assembly: name.to_string(),
+            part: None,
+        })?;
+
+        if let Some(part) = assembly
+            .parts()
+            .into_iter()
+            .find(|part| !self.contains_key(*part))
+        {
+            return Err(BevyError::from(AssemblyNotFoundError {
+                assembly: name.to_string(),
+                part: Some(part.to_string()),
+            }));
+        }
+
+        Ok(AssemblyLoader::new(self, assembly))
+    }
+
+    /// Names of every `ID` that is not a part in this map
+    pub fn missing_ids<ID: PartId>(&self) -> Vec<&'static str> {
+        ID::ALL
+            .iter()
+            .map(|id| id.as_str())
+            .filter(|name| !self.contains_key(*name))
+            .collect()
+    }
+}
+
+// Indexing by name is spelled out since the `PartId` impl below hides the one from `Deref`
+impl<EXTENSION: Asset> Index<&str> for PartsMap<EXTENSION> {
+    type Output = PartPath<EXTENSION>;
+
+    fn index(&self, name: &str) -> &Self::Output {
+        &self.parts[name]
+    }
+}
+
+impl<EXTENSION: Asset> Index<&String> for PartsMap<EXTENSION> {
+    type Output = PartPath<EXTENSION>;
+
+    fn index(&self, name: &String) -> &Self::Output {
+        &self.parts[name]
+    }
+}
+
+// Same as indexing by name, `require_part_ids` makes sure the ids exist before the map is used
+impl<EXTENSION: Asset, ID: PartId> Index<ID> for PartsMap<EXTENSION> {
+    type Output = PartPath<EXTENSION>;
+
+    fn index(&self, id: ID) -> &Self::Output {
+        &self.parts[id.as_str()]
+    }
 }
 
 #[derive(Debug)]
@@ -1,40 +1,77 @@
+use crate::config_loader::{ConfigErrorQueue, PartsMapLoader};
 use crate::config_singleton::config_singleton;
-use crate::{PartsMap, replace_material};
-use bevy::asset::Asset;
+use crate::extend_gltf_material::RegisteredMaterialExtension;
+use crate::hot_reload::hot_reload;
+use crate::part_components::part_components;
+use crate::part_label::part_labels;
+use crate::part_ready::part_ready;
+use crate::preload::preload;
+use crate::socket::sockets;
+use crate::{PartId, PartsMap, SpawnedPart};
+use bevy::asset::{Asset, AssetApp, AssetServer};
 use bevy::pbr::MaterialExtension;
-use bevy::prelude::{Plugin, Reflect, States};
+use bevy::prelude::{App, Plugin, Reflect, States};
 use bevy::render::render_resource::AsBindGroup;
 use bevy::state::state::FreelyMutableState;
-use bevy_common_assets::json::JsonAssetPlugin;
 use serde::{Deserialize, Serialize};
+use std::hash::Hash;
 use std::marker::PhantomData;
 
 pub type SimpleRuntimeGltfLoaderPlugin = RuntimeGlftLoaderPlugin<EmptyExtension>;
 
-pub struct RuntimeGlftLoaderPlugin<EXTENSION, STATE = EmptyState, MATERIAL = EmptyMaterialExtension> {
-    file_ending: &'static str,
+pub struct RuntimeGlftLoaderPlugin<EXTENSION, STATE = EmptyState> {
+    file_endings: Vec<&'static str>,
     // Load a single config as a resource
     load_single: Option<LoadSingleConfig<STATE>>,
-    register_material_extension: bool,
+    // Rebuild the loaded config when its files change on disk
+    hot_reload: Option<HotReloadConfig>,
+    // Kept outside of `load_single` so they can be set before or after `load_single` and `load_many`
+    preload: bool,
+    // Parts that must be in the merged config before moving to `to_state`
+    required_parts: Vec<&'static str>,
+    // Every part must have `data` set
+    require_data: bool,
+    // One entry per type given to `register_material_extension`
+    material_extensions: Vec<RegisteredMaterialExtension>,
+    // Add `MaterialPlugin` for every registered extension that does not have one yet
+    add_material_plugins: bool,
     phantom_extension: PhantomData<EXTENSION>,
-    phantom_material: PhantomData<MATERIAL>,
 }
 
 #[derive(Clone)]
 struct LoadSingleConfig<STATE> {
-    file: &'static str,
+    files: Vec<&'static str>,
     run_in: STATE,
     to_state: STATE,
+    failure_state: Option<STATE>,
 }
 
-impl<EXTENSION, STATE, MATERIAL> RuntimeGlftLoaderPlugin<EXTENSION, STATE, MATERIAL> {
+#[derive(Clone)]
+struct HotReloadConfig {
+    respawn: bool,
+}
+
+impl<EXTENSION, STATE> RuntimeGlftLoaderPlugin<EXTENSION, STATE> {
     pub fn new(file_ending: &'static str) -> Self {
+        Self::with_file_endings(&[file_ending])
+    }
+
+    /// Accept configs with any of the given file endings, the format is picked from the file extension
+    /// `ron`, `toml` and `yaml`/`yml` need their matching cargo feature, anything else is read as JSON
+    pub fn with_file_endings(file_endings: &[&'static str]) -> Self {
         Self {
-            file_ending,
+            file_endings: file_endings
+                .iter()
+                .map(|ending| ending.trim_start_matches('.'))
+                .collect(),
             load_single: None,
-            register_material_extension: false,
+            hot_reload: None,
+            preload: false,
+            required_parts: vec![],
+            require_data: false,
+            material_extensions: vec![],
+            add_material_plugins: false,
             phantom_extension: Default::default(),
-            phantom_material: Default::default(),
         }
     }
 
@@ -46,65 +83,212 @@ impl<EXTENSION, STATE, MATERIAL> RuntimeGlftLoaderPlugin<EXTENSION, STATE, MATER
         file: &'static str,
         run_in: NewState,
         to_state: NewState,
-    ) -> RuntimeGlftLoaderPlugin<EXTENSION, NewState, MATERIAL> {
+    ) -> RuntimeGlftLoaderPlugin<EXTENSION, NewState> {
+        self.load_many(&[file], run_in, to_state)
+    }
+
+    /// Same as `load_single` but loads every file in `files` and merges them into one `PartsMap<EXTENSION>`
+    /// The state only moves to `to_state` once all of the files are loaded
+    /// If a part name is defined in more than one file then the last file in the list wins
+    pub fn load_many<NewState>(
+        self,
+        files: &[&'static str],
+        run_in: NewState,
+        to_state: NewState,
+    ) -> RuntimeGlftLoaderPlugin<EXTENSION, NewState> {
         RuntimeGlftLoaderPlugin {
-            file_ending: self.file_ending,
-            register_material_extension: self.register_material_extension,
+            file_endings: self.file_endings,
+            material_extensions: self.material_extensions,
+            add_material_plugins: self.add_material_plugins,
             load_single: Some(LoadSingleConfig {
-                file,
+                files: files.to_vec(),
                 run_in,
                 to_state,
+                failure_state: None,
             }),
+            hot_reload: self.hot_reload,
+            preload: self.preload,
+            required_parts: self.required_parts,
+            require_data: self.require_data,
             phantom_extension: Default::default(),
-            phantom_material: Default::default(),
         }
     }
 
-    pub fn register_material_extension<NewMaterial>(
-        self,
-    ) -> RuntimeGlftLoaderPlugin<EXTENSION, STATE, NewMaterial> {
-        RuntimeGlftLoaderPlugin {
-            file_ending: self.file_ending,
-            load_single: self.load_single,
-            register_material_extension: true,
-            phantom_extension: Default::default(),
-            phantom_material: Default::default(),
-        }
+    /// Move to `state` when any of the configs given to `load_single` or `load_many` fails to load
+    /// Failures are always sent as a `ConfigLoadError` event, even without a failure state
+    /// Has to be called after `load_single` or `load_many` since those pick the state type
+    pub fn on_load_failure(mut self, state: STATE) -> Self {
+        let Some(load_single) = self.load_single.as_mut() else {
+            panic!("on_load_failure needs load_single or load_many to be called first");
+        };
+        load_single.failure_state = Some(state);
+        self
+    }
+
+    /// Load every model referenced by the configs given to `load_single` or `load_many` before moving to `to_state`
+    /// The loaded models are kept alive in `Res<PreloadedParts<EXTENSION>>` so spawning a part shows it right away
+    /// Once every model is done, a failed model moves to the `on_load_failure` state, without one it is only logged
+    pub fn preload_models(mut self) -> Self {
+        self.preload = true;
+        self
+    }
+
+    /// Check that every id of `ID` is a part in the configs given to `load_single` or `load_many`
+    /// Missing parts are reported as a `ConfigLoadError` and the state moves to the `on_load_failure` state instead of `to_state`
+    pub fn require_part_ids<ID: PartId>(mut self) -> Self {
+        self.required_parts
+            .extend(ID::ALL.iter().map(|id| id.as_str()));
+        self
+    }
+
+    /// Check that every part in `parts` is in the configs given to `load_single` or `load_many`
+    /// All missing parts are reported together as one `ConfigLoadError` before moving to `to_state`
+    pub fn require_parts(mut self, parts: &[&'static str]) -> Self {
+        self.required_parts.extend_from_slice(parts);
+        self
+    }
+
+    /// Check that every loaded part sets `data`, for when `EXTENSION` is not optional for your parts
+    /// Parts without data are reported in the same `ConfigLoadError` as the parts missing from `require_parts`
+    pub fn require_data(mut self) -> Self {
+        self.require_data = true;
+        self
+    }
+
+    /// Keep the loaded config in sync with the files on disk, only works together with `load_single` or `load_many`
+    /// Every rebuild of `PartsMap<EXTENSION>` sends a `PartsMapChanged<EXTENSION>` event
+    /// If `respawn` is set then spawned parts whose path, label, node, transform or components changed are rebuilt
+    /// in place with `PartLoader::replace`
+    /// Requires bevy's `file_watcher` feature and `AssetPlugin::watch_for_changes_override`
+    pub fn hot_reload(mut self, respawn: bool) -> Self {
+        self.hot_reload = Some(HotReloadConfig { respawn });
+        self
+    }
+
+    /// Apply `MATERIAL` to parts loaded with `PartLoader::extend_material::<MATERIAL>`
+    /// Every extended mesh gets its own `ExtendedMaterial`, use `register_shared_material_extension` to share them
+    /// Can be called once for every extension type used, registering the same type twice has no effect
+    pub fn register_material_extension<MATERIAL: MaterialExtension + Clone>(mut self) -> Self
+    where
+        MATERIAL::Data: PartialEq + Eq + Hash + Clone,
+    {
+        self.material_extensions
+            .push(RegisteredMaterialExtension::new::<MATERIAL>());
+        self
+    }
+
+    /// Same as `register_material_extension` but meshes with the same base material and an equal extension value
+    /// share one `ExtendedMaterial` instead of each getting their own, found in `Res<ExtendedMaterialCache<MATERIAL>>`
+    pub fn register_shared_material_extension<
+        MATERIAL: MaterialExtension + Clone + PartialEq + Hash,
+    >(
+        mut self,
+    ) -> Self
+    where
+        MATERIAL::Data: PartialEq + Eq + Hash + Clone,
+    {
+        self.material_extensions
+            .push(RegisteredMaterialExtension::shared::<MATERIAL>());
+        self
+    }
+
+    /// Add `MaterialPlugin::<ExtendedMaterial<StandardMaterial, MATERIAL>>` for every registered extension
+    /// Extensions whose `MaterialPlugin` was already added to the app are skipped
+    pub fn add_material_plugins(mut self) -> Self {
+        self.add_material_plugins = true;
+        self
     }
 }
 
-impl<EXTENSION, STATE, MATERIAL> Default for RuntimeGlftLoaderPlugin<EXTENSION, STATE, MATERIAL> {
+impl<EXTENSION, STATE> Default for RuntimeGlftLoaderPlugin<EXTENSION, STATE> {
     fn default() -> Self {
-        Self::new(".json")
+        Self::with_file_endings(&[
+            "json",
+            #[cfg(feature = "ron")]
+            "ron",
+            #[cfg(feature = "toml")]
+            "toml",
+            #[cfg(feature = "yaml")]
+            "yaml",
+            #[cfg(feature = "yaml")]
+            "yml",
+        ])
     }
 }
 
-impl<EXTENSION, STATE, MATERIAL> Plugin for RuntimeGlftLoaderPlugin<EXTENSION, STATE, MATERIAL>
+impl<EXTENSION, STATE> Plugin for RuntimeGlftLoaderPlugin<EXTENSION, STATE>
 where
     for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
     STATE: States + FreelyMutableState + Clone,
-    MATERIAL: MaterialExtension + Clone,
 {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_plugins(JsonAssetPlugin::<PartsMap<EXTENSION>>::new(&[
-            self.file_ending
-        ]));
+        let errors = app
+            .world_mut()
+            .get_resource_or_init::<ConfigErrorQueue>()
+            .clone();
+        let asset_server = app.world().resource::<AssetServer>().clone();
+        app.init_asset::<PartsMap<EXTENSION>>()
+            .register_asset_loader(PartsMapLoader::<EXTENSION>::new(
+                &self.file_endings,
+                errors,
+                asset_server,
+            ));
+
+        if !app.is_plugin_added::<SpawnedPartsPlugin>() {
+            app.add_plugins(SpawnedPartsPlugin);
+        }
 
         if let Some(LoadSingleConfig {
-            file,
+            files,
             run_in,
             to_state,
+            failure_state,
         }) = self.load_single.clone()
         {
-            config_singleton::<EXTENSION, STATE>(app, file, run_in, to_state);
+            if self.preload {
+                preload::<EXTENSION, STATE>(app, run_in.clone());
+            }
+
+            config_singleton::<EXTENSION, STATE>(
+                app,
+                &files,
+                &self.required_parts,
+                self.require_data,
+                run_in,
+                to_state,
+                failure_state,
+            );
+
+            if let Some(HotReloadConfig { respawn }) = self.hot_reload {
+                hot_reload::<EXTENSION>(app, respawn);
+            }
+        }
+
+        for material_extension in self.material_extensions.iter() {
+            material_extension.build(app, self.add_material_plugins);
         }
+    }
 
-        if self.register_material_extension {
-            app.add_observer(replace_material::<MATERIAL>);
+    fn finish(&self, app: &mut App) {
+        for material_extension in self.material_extensions.iter() {
+            material_extension.finish(app);
         }
     }
 }
 
+// Systems working on spawned parts of any `PartsMap`, added once however many `RuntimeGlftLoaderPlugin` there are
+struct SpawnedPartsPlugin;
+
+impl Plugin for SpawnedPartsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SpawnedPart>();
+        part_labels(app);
+        sockets(app);
+        part_ready(app);
+        part_components(app);
+    }
+}
+
 #[derive(States, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 // Generic state for when users dont want to implement custom state loading
 pub enum EmptyState {}
@@ -115,7 +299,7 @@ pub struct EmptyExtension;
 
 pub type SimplePartsMap = PartsMap<EmptyExtension>;
 
-#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone, PartialEq, Hash)]
 pub struct EmptyMaterialExtension {}
 
 impl MaterialExtension for EmptyMaterialExtension {}
@@ -0,0 +1,90 @@
+use crate::config_singleton::{MoveToState, load_config};
+use crate::progress::PartsLoadingProgress;
+use bevy::asset::{Asset, AssetServer, Handle, RecursiveDependencyLoadState};
+use bevy::gltf::Gltf;
+use bevy::prelude::{
+    Condition, FixedPreUpdate, IntoScheduleConfigs, NextState, Res, ResMut, Resource, States,
+    in_state, resource_exists, warn,
+};
+use bevy::state::state::FreelyMutableState;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+pub(crate) fn preload<EXTENSION, STATE>(app: &mut bevy::prelude::App, run_in: STATE)
+where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+    STATE: States + FreelyMutableState + Clone,
+{
+    app.insert_resource(PreloadModels::<EXTENSION>(Default::default()))
+        .add_systems(
+            FixedPreUpdate,
+            wait_for_models::<EXTENSION, STATE>
+                .after(load_config::<EXTENSION, STATE>)
+                .run_if(in_state(run_in).and(resource_exists::<PreloadedParts<EXTENSION>>)),
+        );
+}
+
+// Marks that `load_config` should preload the models instead of moving to the next state
+#[derive(Resource)]
+pub(crate) struct PreloadModels<EXTENSION>(PhantomData<EXTENSION>);
+
+/// Keeps every model referenced by the loaded `PartsMap<EXTENSION>` alive, keyed by part path
+#[derive(Resource)]
+pub struct PreloadedParts<EXTENSION> {
+    pub handles: HashMap<String, Handle<Gltf>>,
+    phantom_data: PhantomData<EXTENSION>,
+}
+
+impl<EXTENSION> PreloadedParts<EXTENSION> {
+    pub(crate) fn new<'a>(
+        paths: impl Iterator<Item = &'a String>,
+        asset_server: &AssetServer,
+    ) -> Self {
+        Self {
+            handles: paths
+                .map(|path| (path.clone(), asset_server.load(path.clone())))
+                .collect(),
+            phantom_data: Default::default(),
+        }
+    }
+}
+
+fn wait_for_models<EXTENSION, STATE: States + FreelyMutableState + Clone>(
+    preloaded: Res<PreloadedParts<EXTENSION>>,
+    mut progress: ResMut<PartsLoadingProgress<EXTENSION>>,
+    asset_server: Res<AssetServer>,
+    next_state_res: Res<MoveToState<EXTENSION, STATE>>,
+    mut next_state: ResMut<NextState<STATE>>,
+) where
+    for<'de> EXTENSION: serde::Deserialize<'de> + Asset,
+{
+    let mut ready = true;
+    let mut failed = false;
+    let mut loaded = 0;
+    for (path, handle) in preloaded.handles.iter() {
+        match asset_server.recursive_dependency_load_state(handle.id()) {
+            RecursiveDependencyLoadState::Loaded => loaded += 1,
+            // A broken model should not keep the app in the loading state
+            RecursiveDependencyLoadState::Failed(error) => {
+                failed = true;
+                if progress.fail(path, error.to_string()) {
+                    warn!("unable to preload {}: {}", path, error);
+                }
+            }
+            _ => ready = false,
+        }
+    }
+
+    progress.models_total = preloaded.handles.len();
+    progress.models_loaded = loaded;
+
+    if !ready {
+        return;
+    }
+
+    // Without a failure state the models that failed are skipped
+    match (&next_state_res.failed, failed) {
+        (Some(failed_state), true) => next_state.set(failed_state.clone()),
+        _ => next_state.set(next_state_res.next.clone()),
+    }
+}
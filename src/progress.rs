@@ -0,0 +1,62 @@
+use bevy::prelude::Resource;
+use std::marker::PhantomData;
+
+/// How far the configs and models of `PartsMap<EXTENSION>` are in loading, meant for loading screens
+#[derive(Resource)]
+pub struct PartsLoadingProgress<EXTENSION> {
+    pub configs_loaded: usize,
+    pub configs_total: usize,
+    // Set once every config is merged into the `PartsMap`
+    pub config_parsed: bool,
+    // Only counted when models are preloaded
+    pub models_loaded: usize,
+    pub models_total: usize,
+    pub failed: Vec<LoadFailure>,
+    // Set when the merged configs miss parts given to `require_parts` or `require_data`, not counted as a failed file
+    pub validation_error: Option<String>,
+    phantom_data: PhantomData<EXTENSION>,
+}
+
+impl<EXTENSION> PartsLoadingProgress<EXTENSION> {
+    pub(crate) fn new(configs_total: usize) -> Self {
+        Self {
+            configs_loaded: 0,
+            configs_total,
+            config_parsed: false,
+            models_loaded: 0,
+            models_total: 0,
+            failed: vec![],
+            validation_error: None,
+            phantom_data: Default::default(),
+        }
+    }
+
+    /// Loaded and failed files over all known files, between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        let total = self.configs_total + self.models_total;
+        if total == 0 {
+            return 0.0;
+        }
+
+        let done = self.configs_loaded + self.models_loaded + self.failed.len();
+        (done as f32 / total as f32).min(1.0)
+    }
+
+    pub(crate) fn fail(&mut self, path: &str, error: String) -> bool {
+        if self.failed.iter().any(|failure| failure.path == path) {
+            return false;
+        }
+
+        self.failed.push(LoadFailure {
+            path: path.to_string(),
+            error,
+        });
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadFailure {
+    pub path: String,
+    pub error: String,
+}
@@ -0,0 +1,258 @@
+use crate::part_label::PendingPartLabel;
+use bevy::app::{App, Update};
+use bevy::asset::{AssetServer, LoadState};
+use bevy::prelude::{
+    ChildOf, Children, Commands, Component, DetectChanges, Entity, EntityWorldMut, Event,
+    EventWriter, Name, Query, Ref, Res, error,
+};
+use bevy::scene::{SceneInstance, SceneRoot, SceneSpawner};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+// Parents parts to their sockets
+pub(crate) fn sockets(app: &mut App) {
+    app.add_event::<SocketNotFoundError>()
+        .add_systems(Update, attach_to_sockets);
+}
+
+// Part waiting for the scene of `target` to be ready so it can be parented to the `socket` node
+#[derive(Component)]
+pub(crate) struct PendingSocket {
+    target: Entity,
+    socket: String,
+}
+
+impl PendingSocket {
+    pub(crate) fn new(target: Entity, socket: &str) -> Self {
+        Self {
+            target,
+            socket: socket.to_string(),
+        }
+    }
+}
+
+// Socket node a part was attached to, the part is attached again when `target` is replaced
+#[derive(Component)]
+pub(crate) struct AttachedSocket {
+    target: Entity,
+    socket: String,
+}
+
+// The old scene of a replaced part is despawned with everything below it,
+// so parts attached to its sockets are moved to the part root until the new scene is ready
+pub(crate) fn detach_from_sockets(mut entity: EntityWorldMut) {
+    let target = entity.id();
+
+    entity.world_scope(|world| {
+        let mut attached = world.query::<(Entity, &AttachedSocket)>();
+        let parts: Vec<(Entity, String)> = attached
+            .iter(world)
+            .filter(|(_, attached)| attached.target == target)
+            .map(|(part, attached)| (part, attached.socket.clone()))
+            .collect();
+
+        for (part, socket) in parts {
+            // Parts moved somewhere else since are left alone
+            let mut ancestor = part;
+            let below_target = std::iter::from_fn(|| {
+                ancestor = world.get::<ChildOf>(ancestor)?.parent();
+                Some(ancestor)
+            })
+            .any(|ancestor| ancestor == target);
+            if !below_target {
+                continue;
+            }
+
+            world
+                .entity_mut(part)
+                .remove::<AttachedSocket>()
+                .insert((ChildOf(target), PendingSocket::new(target, &socket)));
+        }
+    });
+}
+
+/// Sent when a part given to `PartLoader::on_socket` could not be attached,
+/// either because the socket node is missing or because the scene of `target` failed to load
+/// The part is left unparented
+#[derive(Event, Debug, Clone)]
+pub struct SocketNotFoundError {
+    pub part: Entity,
+    pub target: Entity,
+    pub socket: String,
+}
+
+impl Display for SocketNotFoundError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unable to find socket {} on {} for part {}",
+            &self.socket, self.target, self.part
+        )
+    }
+}
+
+impl Error for SocketNotFoundError {}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn attach_to_sockets(
+    mut commands: Commands,
+    pending: Query<(Entity, &PendingSocket)>,
+    targets: Query<(
+        Option<Ref<SceneRoot>>,
+        Option<&SceneInstance>,
+        Option<&PendingPartLabel>,
+    )>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    scene_spawner: Res<SceneSpawner>,
+    asset_server: Res<AssetServer>,
+    mut errors: EventWriter<SocketNotFoundError>,
+) {
+    for (part, PendingSocket { target, socket }) in pending.iter() {
+        // A despawned target has no descendants and is reported as a missing socket right away
+        if let Ok((scene, instance, label)) = targets.get(*target) {
+            // A scene that failed to load never becomes ready, its sockets are reported missing
+            let failed = scene.as_ref().is_some_and(|scene| {
+                let LoadState::Failed(load_error) = asset_server.load_state(&scene.0) else {
+                    return false;
+                };
+                error!("scene of {} failed to load: {}", target, load_error);
+                true
+            });
+
+            let ready = match (scene, instance) {
+                _ if failed => true,
+                // A swapped scene keeps the old instance until the scene spawner runs
+                (Some(scene), Some(instance)) => {
+                    !scene.is_changed() && scene_spawner.instance_is_ready(**instance)
+                }
+                (Some(_), None) => false,
+                (None, _) => label.is_none(),
+            };
+            if !ready {
+                continue;
+            }
+        }
+
+        commands.entity(part).remove::<PendingSocket>();
+
+        let found = children
+            .iter_descendants(*target)
+            .find(|child| names.get(*child).is_ok_and(|name| name.as_str() == socket));
+
+        match found {
+            Some(socket_entity) => {
+                commands.entity(part).insert((
+                    ChildOf(socket_entity),
+                    AttachedSocket {
+                        target: *target,
+                        socket: socket.clone(),
+                    },
+                ));
+            }
+            None => {
+                let error = SocketNotFoundError {
+                    part,
+                    target: *target,
+                    socket: socket.clone(),
+                };
+                error!("{}", error);
+                errors.write(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::MinimalPlugins;
+    use bevy::asset::{AssetPlugin, Assets, Handle};
+    use bevy::prelude::{Events, World};
+    use bevy::scene::{Scene, ScenePlugin};
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), ScenePlugin))
+            .register_type::<Name>();
+        sockets(&mut app);
+        app
+    }
+
+    fn socket_scene(app: &mut App) -> Handle<Scene> {
+        let mut world = World::new();
+        world.spawn(Name::new("mount"));
+        app.world_mut()
+            .resource_mut::<Assets<Scene>>()
+            .add(Scene::new(world))
+    }
+
+    fn socket_of(app: &App, part: Entity) -> Option<Entity> {
+        let socket = app.world().get::<ChildOf>(part)?.parent();
+        (app.world().get::<Name>(socket)?.as_str() == "mount").then_some(socket)
+    }
+
+    #[test]
+    fn failed_target_scene_is_reported() {
+        let mut app = app();
+        let scene = app
+            .world()
+            .resource::<AssetServer>()
+            .load::<Scene>("missing.scn.unknown");
+        let target = app.world_mut().spawn(SceneRoot(scene)).id();
+        let part = app
+            .world_mut()
+            .spawn(PendingSocket::new(target, "mount"))
+            .id();
+
+        let mut reported = vec![];
+        for _ in 0..20 {
+            app.update();
+            let events = app.world().resource::<Events<SocketNotFoundError>>();
+            reported.extend(events.get_cursor().read(events).map(|error| error.part));
+            if !reported.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        assert_eq!(reported, [part]);
+        assert!(app.world().get::<PendingSocket>(part).is_none());
+    }
+
+    #[test]
+    fn attached_part_survives_a_replaced_scene() {
+        let mut app = app();
+        let scene = socket_scene(&mut app);
+        let target = app.world_mut().spawn(SceneRoot(scene)).id();
+        let part = app
+            .world_mut()
+            .spawn(PendingSocket::new(target, "mount"))
+            .id();
+
+        for _ in 0..3 {
+            app.update();
+        }
+        let old_socket = socket_of(&app, part).expect("part attached to the first scene");
+
+        // Same as `PartLoader::replace`
+        let scene = socket_scene(&mut app);
+        app.world_mut()
+            .commands()
+            .entity(target)
+            .queue(detach_from_sockets)
+            .insert(SceneRoot(scene));
+
+        for _ in 0..3 {
+            app.update();
+        }
+
+        assert!(app.world().get_entity(old_socket).is_err());
+        let new_socket = socket_of(&app, part).expect("part attached to the new scene");
+        assert_ne!(new_socket, old_socket);
+        assert_eq!(
+            app.world().get::<ChildOf>(new_socket).map(ChildOf::parent),
+            Some(target)
+        );
+    }
+}
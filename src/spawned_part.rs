@@ -0,0 +1,35 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Component, Entity, Query, Reflect};
+
+/// Config entry a part entity was spawned from, inserted by `PartLoader::build`
+#[derive(Component, Reflect, Debug, Clone)]
+pub struct SpawnedPart {
+    // Key of the part in the `PartsMap`, empty when the loader was created with `PartLoader::new`
+    pub name: String,
+    pub path: String,
+    // GLTF label that was spawned, set from `PartLoader::asset_label` or the part config
+    pub label: Option<String>,
+    // GLTF node that was spawned instead of a scene
+    pub node: Option<String>,
+}
+
+/// Looks up the live entities spawned from a part
+#[derive(SystemParam)]
+pub struct PartInstances<'w, 's> {
+    parts: Query<'w, 's, (Entity, &'static SpawnedPart)>,
+}
+
+impl PartInstances<'_, '_> {
+    /// Every entity spawned from the part called `name`
+    pub fn of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Entity> + 'a {
+        self.parts
+            .iter()
+            .filter(move |(_, part)| part.name == name)
+            .map(|(entity, _)| entity)
+    }
+
+    /// Every spawned part entity along with where it came from
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &SpawnedPart)> {
+        self.parts.iter()
+    }
+}
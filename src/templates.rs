@@ -0,0 +1,242 @@
+use crate::part_path::PartPath;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Part key naming the part whose values this part starts from
+const EXTENDS_KEY: &str = "extends";
+// Part key marking a part that only exists to be extended, it is left out of the `PartsMap`
+const ABSTRACT_KEY: &str = "abstract";
+
+pub(crate) fn is_template(part: &Value) -> bool {
+    part.get(EXTENDS_KEY).is_some() || part.get(ABSTRACT_KEY).is_some()
+}
+
+// Only the keys of a part that decide how it is read, read first so the other parts never go through `Value`
+#[derive(Deserialize)]
+#[serde(rename = "PartPath")]
+pub(crate) struct PartKeys {
+    #[serde(default, deserialize_with = "present")]
+    extends: bool,
+    #[serde(default, rename = "abstract", deserialize_with = "present")]
+    is_abstract: bool,
+    #[serde(default, deserialize_with = "present")]
+    pub(crate) components: bool,
+}
+
+impl PartKeys {
+    pub(crate) fn is_template(&self) -> bool {
+        self.extends || self.is_abstract
+    }
+}
+
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    IgnoredAny::deserialize(deserializer).map(|_| true)
+}
+
+// Merges every template part with the parts it extends and deserializes the ones that can be spawned
+// Other parts are only used as a base, they are deserialized straight from the file
+// Errors carry the name of the part that could not be resolved
+pub(crate) fn resolve_templates<EXTENSION>(
+    raw: HashMap<String, Value>,
+) -> Result<HashMap<String, PartPath<EXTENSION>>, (String, String)>
+where
+    for<'de> EXTENSION: Deserialize<'de>,
+{
+    let mut resolved = HashMap::new();
+    let mut parts = HashMap::new();
+
+    for (name, part) in raw.iter().filter(|(_, part)| is_template(part)) {
+        let value = resolve_part(name, &raw, &mut resolved, &mut vec![])?;
+
+        if part.get(ABSTRACT_KEY).and_then(Value::as_bool) == Some(true) {
+            continue;
+        }
+
+        let raw_data = value.get("data").filter(|data| !data.is_null()).cloned();
+        let mut part = PartPath::<EXTENSION>::deserialize(value)
+            .map_err(|error| (name.clone(), error.to_string()))?;
+        part.raw_data = raw_data;
+        parts.insert(name.clone(), part);
+    }
+
+    Ok(parts)
+}
+
+fn resolve_part(
+    name: &str,
+    raw: &HashMap<String, Value>,
+    resolved: &mut HashMap<String, Value>,
+    chain: &mut Vec<String>,
+) -> Result<Value, (String, String)> {
+    if let Some(value) = resolved.get(name) {
+        return Ok(value.clone());
+    }
+
+    if chain.iter().any(|parent| parent == name) {
+        return Err((
+            name.to_string(),
+            format!("template cycle {} -> {}", chain.join(" -> "), name),
+        ));
+    }
+
+    let mut value = raw[name].clone();
+    let parent = value.as_object_mut().and_then(|part| {
+        part.remove(ABSTRACT_KEY);
+        part.remove(EXTENDS_KEY)
+    });
+
+    if let Some(parent) = parent {
+        let Some(parent) = parent.as_str() else {
+            return Err((
+                name.to_string(),
+                format!("{} must be the name of a part", EXTENDS_KEY),
+            ));
+        };
+
+        if !raw.contains_key(parent) {
+            return Err((
+                name.to_string(),
+                format!("extended part {} does not exist", parent),
+            ));
+        }
+
+        chain.push(name.to_string());
+        let mut base = resolve_part(parent, raw, resolved, chain)?;
+        chain.pop();
+
+        deep_merge(&mut base, value);
+        value = base;
+    }
+
+    resolved.insert(name.to_string(), value.clone());
+    Ok(value)
+}
+
+// Objects are merged key by key, anything else in `overlay` replaces `base`
+fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EmptyExtension;
+    use serde_json::json;
+
+    fn raw(parts: Value) -> HashMap<String, Value> {
+        serde_json::from_value(parts).unwrap()
+    }
+
+    #[test]
+    fn extended_part_is_merged_and_abstract_part_left_out() {
+        let parts = resolve_templates::<EmptyExtension>(raw(json!({
+            "Car": { "abstract": true, "path": "car.gltf", "node": "Body" },
+            "Plain": { "path": "plain.gltf" },
+            "FastCar": { "extends": "Car", "label": "Scene1" },
+        })))
+        .unwrap();
+
+        // Only template parts are resolved, the loader reads the others itself
+        let mut names: Vec<_> = parts.keys().collect();
+        names.sort();
+        assert_eq!(names, ["FastCar"]);
+
+        let fast_car = &parts["FastCar"];
+        assert_eq!(fast_car.path, "car.gltf");
+        assert_eq!(fast_car.node.as_deref(), Some("Body"));
+        assert_eq!(fast_car.label.as_deref(), Some("Scene1"));
+    }
+
+    #[test]
+    fn template_chain_is_resolved_through_every_parent() {
+        let parts = resolve_templates::<EmptyExtension>(raw(json!({
+            "Base": { "abstract": true, "path": "base.gltf", "node": "Root" },
+            "Middle": { "abstract": true, "extends": "Base", "path": "middle.gltf" },
+            "Leaf": { "extends": "Middle" },
+        })))
+        .unwrap();
+
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts["Leaf"].path, "middle.gltf");
+        assert_eq!(parts["Leaf"].node.as_deref(), Some("Root"));
+    }
+
+    #[test]
+    fn template_cycle_is_an_error() {
+        let (part, message) = resolve_templates::<EmptyExtension>(raw(json!({
+            "A": { "extends": "B", "path": "a.gltf" },
+            "B": { "extends": "A", "path": "b.gltf" },
+        })))
+        .unwrap_err();
+
+        assert!(part == "A" || part == "B");
+        assert!(message.starts_with("template cycle"), "{}", message);
+    }
+
+    #[test]
+    fn missing_parent_is_an_error() {
+        let error = resolve_templates::<EmptyExtension>(raw(json!({
+            "A": { "extends": "Nope", "path": "a.gltf" },
+        })))
+        .unwrap_err();
+
+        assert_eq!(
+            error,
+            (
+                "A".to_string(),
+                "extended part Nope does not exist".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn deep_merge_merges_objects_and_replaces_the_rest() {
+        let mut base = json!({
+            "path": "base.gltf",
+            "data": { "speed": 10.0, "color": "red", "tags": ["a", "b"] },
+        });
+        deep_merge(
+            &mut base,
+            json!({ "data": { "speed": 20.0, "tags": ["c"] }, "node": "Body" }),
+        );
+
+        assert_eq!(
+            base,
+            json!({
+                "path": "base.gltf",
+                "node": "Body",
+                "data": { "speed": 20.0, "color": "red", "tags": ["c"] },
+            })
+        );
+    }
+
+    #[test]
+    fn template_keys_are_found_without_reading_the_part() {
+        let keys: HashMap<String, PartKeys> = serde_json::from_value(json!({
+            "Plain": { "path": "a.gltf", "transform": { "rotation": { "euler": [0.0, 90.0, 0.0] } } },
+            "Child": { "extends": "Plain" },
+            "Base": { "abstract": false, "path": "b.gltf", "components": { "game::Team": "Red" } },
+        }))
+        .unwrap();
+
+        assert!(!keys["Plain"].is_template());
+        assert!(keys["Child"].is_template());
+        assert!(keys["Base"].is_template());
+        assert!(!keys["Plain"].components);
+        assert!(keys["Base"].components);
+    }
+}